@@ -1,28 +1,43 @@
 //! Main file for the appointment system
 
-use std::collections::{BTreeMap, BTreeSet};
-
-use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime, Timelike};
-use lazy_static::lazy_static;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufRead, Write},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
+
+use chrono::{
+    DateTime,
+    Datelike,
+    Duration,
+    FixedOffset,
+    NaiveDate,
+    NaiveDateTime,
+    NaiveTime,
+    Timelike,
+};
 use rand::Rng;
-
-use crate::utils::{end_of_week, now_next_15_mark};
-
-lazy_static! {
-    // Static variable to hold the working hours. 8:00 to 12:00 and 13:00 to 17:00
-    static ref WORKING_HOURS: [(NaiveTime, NaiveTime); 2] = [
-        (
-            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
-            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
-        ),
-        (
-            NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
-            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
-        ),
-    ];
-
-    // Static variable to hold the working days. Monday to Friday
-    static ref WORKING_DAYS: [u32; 5] = [0, 1, 2, 3, 4];
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::{AuditEntry, AuditLog, AuditOperation},
+    catalog::AppointmentCatalog,
+    config::ClinicConfig,
+    error::{CalendarError, ScheduleError},
+    interval_index::IntervalIndex,
+    schedule::WorkingSchedule,
+    utils::{end_of_week_from, next_15_mark_from, optional_duration_minutes, Clock, SystemClock},
+    waitlist::Waitlist,
+};
+
+/// Get the default working schedule, computed once. Monday to Friday, 8:00 to
+/// 12:00 and 13:00 to 17:00
+fn default_schedule() -> &'static WorkingSchedule {
+    static DEFAULT_SCHEDULE: OnceLock<WorkingSchedule> = OnceLock::new();
+
+    DEFAULT_SCHEDULE.get_or_init(WorkingSchedule::default)
 }
 
 // Trait to define the working day times utility functions
@@ -40,26 +55,38 @@ pub trait WorkingDayTimes {
     fn get_next_working_datetime(&self, appointment_type: Option<AppointmentType>)
         -> NaiveDateTime;
 
+    // Iterate over the working time slots from the current date and time up to
+    // `to`
+    fn working_slots_until(&self, to: NaiveDateTime) -> impl Iterator<Item = NaiveDateTime>;
+
+    // Snap the current date and time into a working time slot, without advancing
+    fn snap_to_working_slot(&self) -> Option<NaiveDateTime>;
+
     // Function to append to `to` time the appointment duration
     fn calculate_end_time(self, appointment_type: AppointmentType) -> NaiveDateTime;
+
+    // Function to get the end time of an appointment, failing if it doesn't fit
+    // in a single working hours window
+    fn calculate_end_time_respecting_breaks(
+        self,
+        appointment_type: AppointmentType,
+    ) -> Result<NaiveDateTime, CalendarError>;
 }
 
 impl WorkingDayTimes for NaiveDateTime {
     /// Check if the current date is a working day
     fn is_working_day(&self) -> bool {
-        WORKING_DAYS.contains(&self.date().weekday().num_days_from_monday())
+        default_schedule().is_working_day(self.date())
     }
 
     /// Check if the current time is within the working hours
     fn is_working_hour(&self) -> bool {
-        WORKING_HOURS
-            .iter()
-            .any(|(start, end)| self.time() >= *start && self.time() < *end)
+        default_schedule().is_working_hour(self.time())
     }
 
     /// Check if the current date and time is within the working hours
     fn is_working_day_and_hour(&self) -> bool {
-        self.is_working_day() && self.is_working_hour()
+        default_schedule().is_working_day_and_hour(*self)
     }
 
     /// Get the next working date and time
@@ -70,71 +97,285 @@ impl WorkingDayTimes for NaiveDateTime {
         &self,
         appointment_type: Option<AppointmentType>,
     ) -> NaiveDateTime {
-        // Get the current date and time
-        let mut current = *self;
-
-        // Round to the last 15 minute time
-        current = current.date().and_time(
-            NaiveTime::from_hms_opt(
-                current.time().hour(),
-                (current.time().minute() / 15) * 15,
-                0,
-            )
-            .unwrap_or(current.time()),
-        );
+        default_schedule().next_working_datetime(*self, appointment_type)
+    }
 
-        // Get the time slot duration. If the appointment type is not provided, use the
-        // default time slot duration of 15 minutes
-        let time_slot_duration = if let Some(appointment_type) = appointment_type {
-            appointment_type.duration()
-        } else {
-            // Default time slot duration is 15 minutes
-            Duration::minutes(15)
-        };
+    /// Iterate over the working 15 minute time slots from the current date and
+    /// time up to, but excluding, `to`
+    ///
+    /// The current date and time is the first time slot if it's within the
+    /// working hours
+    fn working_slots_until(&self, to: NaiveDateTime) -> impl Iterator<Item = NaiveDateTime> {
+        default_schedule().working_slots(*self, to)
+    }
 
-        // Append time slot duration to the current time
-        current += time_slot_duration;
+    /// Snap the current date and time into a working 15 minute time slot
+    ///
+    /// Unlike `get_next_working_datetime`, which always advances by a time
+    /// slot before looking for working hours, this returns the current time
+    /// itself if it's already a valid working time slot. Times that are not on
+    /// a 15 minute mark are rounded up to the next one. Returns `None` if no
+    /// working time slot is found within a week
+    fn snap_to_working_slot(&self) -> Option<NaiveDateTime> {
+        default_schedule().snap_to_working_slot(*self)
+    }
 
-        if current.is_working_day_and_hour() {
-            return current;
-        }
+    /// Function to append to `to` time the appointment duration
+    fn calculate_end_time(self, appointment_type: AppointmentType) -> NaiveDateTime {
+        self + appointment_type.duration()
+    }
 
-        // Check if time is before the break
-        if current.time() < WORKING_HOURS[0].0 {
-            // Set the time to the start of the working hours
-            current = current.date().and_time(WORKING_HOURS[0].0);
-        } else if current.time() < WORKING_HOURS[1].0 {
-            // Set the time to the start of the working hours
-            current = current.date().and_time(WORKING_HOURS[1].0);
-        } else {
-            // Already past end of working day.
-            // Set the time to the start of the next working hours
-            current = current.date().and_time(WORKING_HOURS[0].0);
+    /// Get the end time of an appointment starting at the current date and
+    /// time
+    ///
+    /// Unlike `calculate_end_time`, the appointment must fit within a single
+    /// working hours window. An appointment running into the break returns
+    /// `CalendarError::SpansBreak`, and one running past the end of the
+    /// working day returns `CalendarError::OutsideWorkingHours`
+    fn calculate_end_time_respecting_breaks(
+        self,
+        appointment_type: AppointmentType,
+    ) -> Result<NaiveDateTime, CalendarError> {
+        default_schedule().end_time_respecting_breaks(self, appointment_type.duration())
+    }
+}
 
-            // Get the next working day
-            loop {
-                current += Duration::days(1);
 
-                if current.is_working_day() {
-                    break;
-                }
-            }
-        }
+/// Check if the duration is a positive multiple of 15 minutes
+fn is_valid_duration(duration: Duration) -> bool {
+    duration > Duration::zero() && duration.num_seconds() % (15 * 60) == 0
+}
+
+/// Check if the `next` 15 minute time slot directly follows the `previous` one
+fn are_connected(previous: NaiveDateTime, next: NaiveDateTime) -> bool {
+    next - previous == Duration::minutes(15)
+}
+
+/// Get how long two appointments overlap, negative if they don't
+fn overlap_length(a: &DoctorsAppointment, b: &DoctorsAppointment) -> Duration {
+    a.end_time().min(b.end_time()) - a.date_time.max(b.date_time)
+}
+
+/// Get the start of the window containing the time slot, flooring the time of
+/// the day to the window size in minutes
+fn window_start(time_slot: NaiveDateTime, window_minutes: i64) -> NaiveDateTime {
+    let minutes_of_day = (time_slot.time() - NaiveTime::MIN).num_minutes();
+
+    time_slot.date().and_time(NaiveTime::MIN)
+        + Duration::minutes(minutes_of_day / window_minutes * window_minutes)
+}
+
+/// Lazily walk the working time slots from the `from` time to the `to` time,
+/// keeping the ones where the appointment type fits in available time slots
+/// and ends by the `to` time
+fn walk_free_slots<'a>(
+    schedule: &'a WorkingSchedule,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    duration: Duration,
+    is_available: impl Fn(NaiveDateTime) -> bool + 'a,
+) -> impl Iterator<Item = NaiveDateTime> + 'a {
+    schedule.working_slots(from, to).filter(move |time_slot| {
+        // Check if the following time slots are available for the appointment
+        // to fit, counting a partial time slot as a whole one
+        (0..(duration.num_minutes() + 14) / 15)
+            .map(|index| *time_slot + Duration::minutes(15 * index))
+            .all(|current| {
+                current < to && schedule.is_working_day_and_hour(current) && is_available(current)
+            })
+    })
+}
+
+/// Get the list of free time slots for the appointment type, given the busy
+/// intervals of the calendar
+///
+/// Does the same as `DoctorsCalendar::free_slots`, without storing the
+/// appointments in a calendar. Every busy interval, from its start until its
+/// end, reserves the time slots it covers. Appointments must end by the `to`
+/// time
+pub fn free_slots_for(
+    busy: &[(NaiveDateTime, NaiveDateTime)],
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    appointment_type: AppointmentType,
+    schedule: &WorkingSchedule,
+) -> Vec<NaiveDateTime> {
+    walk_free_slots(
+        schedule,
+        from,
+        to,
+        appointment_type.duration(),
+        |time_slot| {
+            !busy
+                .iter()
+                .any(|(start, end)| time_slot < *end && time_slot + Duration::minutes(15) > *start)
+        },
+    )
+    .collect()
+}
+
+/// Get the free time slots of the appointment type, for the given time period,
+/// that are free on both calendars, e.g. for a procedure needing two doctors
+pub fn common_free_slots(
+    a: &DoctorsCalendar,
+    b: &DoctorsCalendar,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    appointment_type: AppointmentType,
+) -> Vec<NaiveDateTime> {
+    let b_free_slots: BTreeSet<NaiveDateTime> =
+        b.free_slots_iter(from, to, appointment_type).collect();
+
+    a.free_slots_iter(from, to, appointment_type)
+        .filter(|time_slot| b_free_slots.contains(time_slot))
+        .collect()
+}
+
+/// Version of the appointments schema written by
+/// `DoctorsCalendar::export_jsonl`
+///
+/// Version 1 appointments, written without a schema version, may only have a
+/// date and time and an appointment type. Version 2 appointments may also have
+/// a patient, a duration, tags, a status, an id and a creation time
+pub const APPOINTMENT_SCHEMA_VERSION: u64 = 2;
+
+/// Appointment as written by `DoctorsCalendar::export_jsonl`, with the version
+/// of its schema
+#[derive(Serialize)]
+struct VersionedAppointment<'a> {
+    schema_version: u64,
+    #[serde(flatten)]
+    appointment: &'a DoctorsAppointment,
+}
 
-        current
+/// Read an appointment written with any version of the appointments schema,
+/// filling the fields missing from older versions with their defaults
+fn migrate_appointment(mut value: serde_json::Value) -> io::Result<DoctorsAppointment> {
+    let schema_version = match value.as_object_mut() {
+        Some(fields) => match fields.remove("schema_version") {
+            Some(version) => version.as_u64().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid schema version")
+            })?,
+            None => 1,
+        },
+        None => 1,
+    };
+
+    match schema_version {
+        // The fields added since version 1 are optional, and default when
+        // missing: no patient, tags nor id, the type's duration and scheduled
+        1 | APPOINTMENT_SCHEMA_VERSION => Ok(serde_json::from_value(value)?),
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported schema version {}", version),
+        )),
     }
+}
 
-    /// Function to append to `to` time the appointment duration
-    fn calculate_end_time(self, appointment_type: AppointmentType) -> NaiveDateTime {
-        self + appointment_type.duration()
+/// Contact details of the patient an appointment is booked for
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
+pub struct Patient {
+    pub name: String,
+    pub phone: String,
+}
+
+impl Patient {
+    /// Create a new patient
+    pub fn new(name: &str, phone: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            phone: phone.to_string(),
+        }
+    }
+}
+
+/// Lifecycle state of an appointment
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub enum AppointmentStatus {
+    /// Booked and yet to happen
+    #[default]
+    Scheduled,
+    /// The patient attended the appointment
+    Completed,
+    /// The patient didn't show up
+    NoShow,
+    /// The appointment was called off, but kept for reporting
+    Cancelled,
+}
+
+/// Why an appointment was cancelled
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub enum CancellationReason {
+    /// The patient asked to cancel
+    PatientRequest,
+    /// The practice is closed
+    ClinicClosure,
+    /// The patient didn't show up
+    NoShow,
+}
+
+impl CancellationReason {
+    /// Get the status of an appointment cancelled for this reason
+    pub fn status(&self) -> AppointmentStatus {
+        match self {
+            CancellationReason::PatientRequest | CancellationReason::ClinicClosure => {
+                AppointmentStatus::Cancelled
+            },
+            CancellationReason::NoShow => AppointmentStatus::NoShow,
+        }
     }
 }
 
+impl AppointmentStatus {
+    /// Check if an appointment with this status reserves its time slots
+    ///
+    /// Cancelled and no-show appointments free their time slots, so they can
+    /// be booked again
+    pub fn occupies_time(&self) -> bool {
+        matches!(
+            self,
+            AppointmentStatus::Scheduled | AppointmentStatus::Completed
+        )
+    }
+}
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+/// Doctor's appointment
+///
+/// Appointments are compared by value and ordered by their date and time
+/// first, then by their other fields. The id is the identity of the
+/// appointment in the calendar, and is not part of its value: use
+/// `is_same_appointment` to check if two values are versions of the same
+/// appointment, e.g. before and after its status changed. The creation time
+/// is bookkeeping, and not part of the value either.
+///
+/// The calendar keys its appointments on their date and time, then their id
+/// (see `AppointmentKey`), so changing any other field never moves an
+/// appointment, and appointments with the same value are kept apart. Several
+/// appointments can start at the same time, e.g. a cancelled one and the one
+/// booked in its place, but only one of them can occupy its time slots,
+/// unless double-booked with `DoctorsCalendar::force_book`
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DoctorsAppointment {
     pub date_time: NaiveDateTime,
     pub appointment_type: AppointmentType,
+    #[serde(default)]
+    pub patient: Option<Patient>,
+    #[serde(
+        default,
+        rename = "duration_minutes",
+        skip_serializing_if = "Option::is_none",
+        with = "optional_duration_minutes"
+    )]
+    duration_override: Option<Duration>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub status: AppointmentStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<AppointmentId>,
+    /// When the appointment was added to the calendar, according to its clock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<NaiveDateTime>,
 }
 
 impl DoctorsAppointment {
@@ -143,16 +384,115 @@ impl DoctorsAppointment {
         Self {
             date_time,
             appointment_type,
+            patient: None,
+            duration_override: None,
+            tags: BTreeSet::new(),
+            status: AppointmentStatus::Scheduled,
+            id: None,
+            created_at: None,
+        }
+    }
+
+    /// Create a new doctor's appointment, checking that it's plausible
+    ///
+    /// Unlike `new`, the appointment must start on a 15 minute mark and end by
+    /// midnight. Whether it fits in the calendar is still only checked by
+    /// `DoctorsCalendar::add_appointment`
+    pub fn try_new(
+        date_time: NaiveDateTime,
+        appointment_type: AppointmentType,
+    ) -> Result<Self, CalendarError> {
+        if !date_time.minute().is_multiple_of(15)
+            || date_time.second() != 0
+            || date_time.nanosecond() != 0
+        {
+            return Err(CalendarError::UnalignedStart(date_time));
         }
+
+        let appointment = Self::new(date_time, appointment_type);
+
+        // The appointment can't run into the next day
+        if appointment.end_time() > date_time.date().and_time(NaiveTime::MIN) + Duration::days(1) {
+            return Err(CalendarError::OutsideWorkingHours);
+        }
+
+        Ok(appointment)
+    }
+
+    /// Create a new doctor's appointment with a duration different from the
+    /// appointment type's default
+    ///
+    /// Only urgent appointments can override their duration, and the duration
+    /// must be a positive multiple of 15 minutes
+    pub fn new_with_duration(
+        date_time: NaiveDateTime,
+        appointment_type: AppointmentType,
+        duration: Duration,
+    ) -> Result<Self, CalendarError> {
+        if appointment_type != AppointmentType::UrgentDentalAppointment
+            || !is_valid_duration(duration)
+        {
+            return Err(CalendarError::InvalidDuration);
+        }
+
+        Ok(Self {
+            duration_override: Some(duration),
+            ..Self::new(date_time, appointment_type)
+        })
+    }
+
+    /// Get the duration of the appointment
+    ///
+    /// This is the overridden duration if there is one, otherwise the
+    /// appointment type's default
+    pub fn duration(&self) -> Duration {
+        self.duration_override
+            .unwrap_or(self.appointment_type.duration())
+    }
+
+    /// Get the end time of the appointment
+    pub fn end_time(&self) -> NaiveDateTime {
+        self.date_time + self.duration()
+    }
+
+    /// Check if the appointment overlaps in time with another appointment
+    pub fn overlaps(&self, other: &DoctorsAppointment) -> bool {
+        self.date_time < other.end_time() && other.date_time < self.end_time()
+    }
+
+    /// Set the patient the appointment is booked for
+    pub fn with_patient(mut self, patient: Patient) -> Self {
+        self.patient = Some(patient);
+        self
+    }
+
+    /// Add a tag to the appointment, e.g. "new-patient" or "follow-up"
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tags.insert(tag.to_string());
+        self
+    }
+
+    /// Check if both appointments have the same id, i.e. are versions of the
+    /// same appointment in the calendar
+    ///
+    /// Appointments that were never added to a calendar have no id, and are
+    /// not the same as any other appointment
+    pub fn is_same_appointment(&self, other: &DoctorsAppointment) -> bool {
+        self.id.is_some() && self.id == other.id
+    }
+
+    /// Get the key of the appointment in the calendar
+    pub(crate) fn key(&self) -> AppointmentKey {
+        (self.date_time, self.id)
     }
 
     /// Convert the appointment into reserved time slots of 15 minutes
-    pub fn to_reserved_time_slots(self) -> Vec<NaiveDateTime> {
+    pub fn to_reserved_time_slots(&self) -> Vec<NaiveDateTime> {
         let mut time_slots = vec![];
 
         let mut current = self.date_time;
 
-        while current < self.date_time + self.appointment_type.duration() {
+        while current < self.end_time() {
             time_slots.push(current);
             current += Duration::minutes(15);
         }
@@ -161,10 +501,58 @@ impl DoctorsAppointment {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+impl DoctorsAppointment {
+    /// Get the fields the appointment is compared by, i.e. all but the id and
+    /// the creation time
+    fn value(
+        &self,
+    ) -> (
+        &NaiveDateTime,
+        &AppointmentType,
+        &Option<Patient>,
+        &Option<Duration>,
+        &BTreeSet<String>,
+        &AppointmentStatus,
+    ) {
+        (
+            &self.date_time,
+            &self.appointment_type,
+            &self.patient,
+            &self.duration_override,
+            &self.tags,
+            &self.status,
+        )
+    }
+}
+
+impl PartialEq for DoctorsAppointment {
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl Eq for DoctorsAppointment {}
+
+impl PartialOrd for DoctorsAppointment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DoctorsAppointment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value().cmp(&other.value())
+    }
+}
+
+/// Type of an appointment
+///
+/// The types are ordered from the longest to the shortest duration, like the
+/// optimizer's priority, so implants come first
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
 pub enum AppointmentType {
-    DentalCheckUp,
     ImplantConsultation,
+    DentalCheckUp,
     UrgentDentalAppointment,
 }
 
@@ -200,6 +588,12 @@ impl Iterator for AppointmentTypeIter {
 }
 
 impl AppointmentType {
+    /// Iterate through all the appointment types, from the longest to the
+    /// shortest duration
+    pub fn all_by_duration_desc() -> impl Iterator<Item = AppointmentType> {
+        AppointmentTypeIter::new()
+    }
+
     /// Get the duration of the appointment type
     pub fn duration(&self) -> Duration {
         match self {
@@ -210,7 +604,7 @@ impl AppointmentType {
     }
 
     /// Get the display name of the appointment type
-    pub fn display_name(&self) -> &str {
+    pub fn display_name(&self) -> &'static str {
         match self {
             AppointmentType::DentalCheckUp => "Check-up",
             AppointmentType::ImplantConsultation => "Implant Consultation",
@@ -218,306 +612,2838 @@ impl AppointmentType {
         }
     }
 
-    /// Get the duration of the appointment type in 15 minute time slots
-    pub fn duration_in_time_slots(&self) -> u8 {
-        (self.duration().num_minutes() / 15) as u8
-    }
-}
+    /// Get the color of the appointment type, as a hex RGB color, for color
+    /// coding the appointment types in a calendar view
+    pub fn color(&self) -> &'static str {
+        match self {
+            AppointmentType::DentalCheckUp => "#2e7d32",
+            AppointmentType::ImplantConsultation => "#1565c0",
+            AppointmentType::UrgentDentalAppointment => "#c62828",
+        }
+    }
+
+    /// Get the duration of the appointment type in 15 minute time slots
+    pub fn duration_in_time_slots(&self) -> u8 {
+        (self.duration().num_minutes() / 15) as u8
+    }
+}
+
+/// Time blocked in the calendar that is not a patient appointment, e.g. admin
+/// work or an extended lunch
+///
+/// Blocks reduce the availability, but are not returned as booked
+/// appointments. They may overlap each other.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct Block {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub label: String,
+}
+
+impl Block {
+    /// Create a new block from `start` until `end`
+    pub fn new(start: NaiveDateTime, end: NaiveDateTime, label: &str) -> Self {
+        Self {
+            start,
+            end,
+            label: label.to_string(),
+        }
+    }
+
+    /// Create a block closing the practice on the given date, for the whole
+    /// day or only the given time window
+    pub fn closure(date: NaiveDate, window: Option<(NaiveTime, NaiveTime)>) -> Self {
+        let (start, end) = match window {
+            Some((start, end)) => (date.and_time(start), date.and_time(end)),
+            None => {
+                let start = date.and_time(NaiveTime::MIN);
+
+                (start, start + Duration::days(1))
+            },
+        };
+
+        Self::new(start, end, "Closed")
+    }
+
+    /// Check if the block covers any part of the 15 minute time slot
+    pub fn covers(&self, time_slot: NaiveDateTime) -> bool {
+        time_slot < self.end && time_slot + Duration::minutes(15) > self.start
+    }
+}
+
+/// Result of cancelling an appointment
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cancellation {
+    /// The cancelled appointment
+    pub appointment: DoctorsAppointment,
+    /// Waitlisted patients, with the appointment type they wait for, that can
+    /// now be booked using the freed time
+    pub waitlisted: Vec<(AppointmentType, Patient)>,
+}
+
+/// Slot chosen by the optimizer for a window
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OptimizedSlot {
+    /// Start of the window
+    pub window_start: NaiveDateTime,
+    /// The chosen free slot
+    pub slot: NaiveDateTime,
+    /// Number of appointments per type that still fit in the free time
+    /// connected to the chosen slot, counted from the longest to the shortest
+    /// type
+    pub capacity: BTreeMap<AppointmentType, u8>,
+}
+
+/// Key of an appointment in the calendar, its date and time then its id
+pub type AppointmentKey = (NaiveDateTime, Option<AppointmentId>);
+
+/// Identifier of an appointment, assigned when it's added to the calendar
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Serialize, Deserialize)]
+pub struct AppointmentId(u64);
+
+/// Identifier of a hold, returned by `DoctorsCalendar::hold`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct HoldId(u64);
+
+/// Temporary reservation of an appointment's time slots, e.g. while the patient
+/// fills in the booking form
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Hold {
+    pub appointment: DoctorsAppointment,
+    pub expires_at: NaiveDateTime,
+}
+
+impl Hold {
+    /// Check if the hold reserves the 15 minute time slot at the given time
+    fn covers(&self, time_slot: NaiveDateTime, now: NaiveDateTime) -> bool {
+        now < self.expires_at
+            && self.appointment.date_time <= time_slot
+            && time_slot < self.appointment.end_time()
+    }
+}
+
+/// Strategy to place an appointment when booking it without a specific time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlacementStrategy {
+    /// The earliest free time slot
+    #[default]
+    FirstFit,
+    /// The time slot chosen by `free_slots_optimized` in the earliest 60
+    /// minute window, which keeps the most room for long-duration
+    /// appointments around it
+    BestFit,
+}
+
+/// Policy for imported appointment durations that are not a multiple of 15
+/// minutes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingPolicy {
+    /// Round up to the next multiple of 15 minutes
+    RoundUp,
+    /// Round to the nearest multiple of 15 minutes, halfway durations up
+    RoundNearest,
+    /// Keep the duration, so the appointment is rejected
+    #[default]
+    Reject,
+}
+
+impl RoundingPolicy {
+    /// Round the duration according to the policy
+    pub fn apply(&self, duration: Duration) -> Duration {
+        let minutes = duration.num_minutes();
+
+        match self {
+            RoundingPolicy::RoundUp => Duration::minutes((minutes + 14).div_euclid(15) * 15),
+            RoundingPolicy::RoundNearest => Duration::minutes((minutes + 7).div_euclid(15) * 15),
+            RoundingPolicy::Reject => duration,
+        }
+    }
+}
+
+/// Read-only view of the calendar at the time it was taken
+///
+/// Taking a snapshot is cheap, as the appointments and blocks are shared with
+/// the calendar. The calendar copies them the next time it's changed
+/// (clone-on-write), so the snapshot never sees later changes and all its
+/// queries are answered from the same state. Only the expiry of holds is
+/// evaluated against the clock at query time
+#[derive(Clone)]
+pub struct CalendarSnapshot {
+    calendar: DoctorsCalendar,
+}
+
+impl CalendarSnapshot {
+    /// Get the list of booked appointments
+    pub fn booked_appointments(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> Vec<DoctorsAppointment> {
+        self.calendar.booked_appointments(from, to)
+    }
+
+    /// Get the list of free time slots for the given time period and
+    /// appointment type
+    pub fn free_slots(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> Vec<NaiveDateTime> {
+        self.calendar.free_slots(from, to, appointment_type)
+    }
+
+    /// Return the free slots, filtered to one appointment per 60 minute window.
+    /// The priority goes to the long-duration appointments
+    pub fn free_slots_optimized(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> Vec<NaiveDateTime> {
+        self.calendar
+            .free_slots_optimized(from, to, appointment_type)
+    }
+
+    /// Get the first free time slot for the appointment type, starting from
+    /// the given date and time
+    pub fn next_available(
+        &self,
+        after: NaiveDateTime,
+        appointment_type: AppointmentType,
+    ) -> Option<NaiveDateTime> {
+        self.calendar.next_available(after, appointment_type)
+    }
+
+    /// Get the first free time slot of every appointment type, starting from
+    /// the given date and time
+    pub fn next_available_all_types(
+        &self,
+        after: NaiveDateTime,
+    ) -> BTreeMap<AppointmentType, Option<NaiveDateTime>> {
+        self.calendar.next_available_all_types(after)
+    }
+}
+
+/// Time interval of a free/busy document, serialized as RFC 3339 timestamps
+/// with the practice's UTC offset
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct FreeBusyInterval {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+impl FreeBusyInterval {
+    /// Create an interval from the practice's wall clock times, at the given
+    /// UTC offset of the practice
+    pub fn new(start: NaiveDateTime, end: NaiveDateTime, offset: FixedOffset) -> Self {
+        Self {
+            start: start.and_local_timezone(offset).unwrap(),
+            end: end.and_local_timezone(offset).unwrap(),
+        }
+    }
+}
+
+/// Free and busy working time of the calendar, as returned by
+/// `DoctorsCalendar::freebusy_json`
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct FreeBusy {
+    /// Working time reserved by appointments, blocked or held
+    pub busy: Vec<FreeBusyInterval>,
+    /// Working time free to book
+    pub free: Vec<FreeBusyInterval>,
+}
+
+/// Merge `(start, end)` intervals, sorted by their start, into sorted,
+/// non-overlapping intervals
+///
+/// Intervals that touch or overlap are merged, empty ones are skipped
+fn merge_intervals(
+    intervals: impl IntoIterator<Item = (NaiveDateTime, NaiveDateTime)>,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut merged: Vec<(NaiveDateTime, NaiveDateTime)> = vec![];
+
+    for (start, end) in intervals {
+        if start >= end {
+            continue;
+        }
+
+        match merged.last_mut() {
+            // Extend the last interval if this one touches or overlaps it
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+}
+
+/// Difference between the appointments of two calendars
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct CalendarDiff {
+    /// Appointments only in the other calendar, in chronological order
+    pub added: Vec<DoctorsAppointment>,
+    /// Appointments only in this calendar, in chronological order
+    pub removed: Vec<DoctorsAppointment>,
+}
+
+impl CalendarDiff {
+    /// Check if both calendars have the same appointments
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl TryFrom<(NaiveDateTime, AppointmentType)> for DoctorsAppointment {
+    type Error = CalendarError;
+
+    /// Same as `DoctorsAppointment::try_new`
+    fn try_from(
+        (date_time, appointment_type): (NaiveDateTime, AppointmentType),
+    ) -> Result<Self, Self::Error> {
+        Self::try_new(date_time, appointment_type)
+    }
+}
+
+impl FromStr for AppointmentType {
+    type Err = CalendarError;
+
+    /// Parse an appointment type from its display name or a short alias
+    ///
+    /// Accepts "checkup", "check-up", "implant" and "urgent", as well as the
+    /// display names, case-insensitively
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+
+        // Check the aliases first, then fall back to the display names
+        let appointment_type = match name.as_str() {
+            "checkup" | "check-up" => Some(AppointmentType::DentalCheckUp),
+            "implant" => Some(AppointmentType::ImplantConsultation),
+            "urgent" => Some(AppointmentType::UrgentDentalAppointment),
+            _ => AppointmentType::all_by_duration_desc()
+                .find(|appointment_type| appointment_type.display_name().to_lowercase() == name),
+        };
+
+        appointment_type.ok_or_else(|| CalendarError::UnknownAppointmentType(s.to_string()))
+    }
+}
+
+// Define the doctor's calendar
+#[derive(Clone)]
+pub struct DoctorsCalendar {
+    // Keyed on their date and time, then their id, see `DoctorsAppointment`
+    appointments: Arc<BTreeMap<AppointmentKey, DoctorsAppointment>>,
+    // Cancelled appointments, kept for record-keeping
+    archived: Vec<(DoctorsAppointment, CancellationReason)>,
+    pub waitlist: Waitlist,
+    blocks: Arc<BTreeSet<Block>>,
+    holds: BTreeMap<HoldId, Hold>,
+    next_hold_id: u64,
+    next_appointment_id: u64,
+    // Appointments occupying their time slots, by the time slots they cover
+    occupancy: Arc<IntervalIndex>,
+    clock: Arc<dyn Clock>,
+    config: Arc<ClinicConfig>,
+    audit_log: Option<AuditLog>,
+    optimizer_window: Duration,
+    overlap_tolerance: Duration,
+    // Incremented on every change, for optimistic concurrency
+    version: u64,
+}
+
+impl Default for DoctorsCalendar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> IntoIterator for &'a DoctorsCalendar {
+    type IntoIter = std::collections::btree_map::Values<'a, AppointmentKey, DoctorsAppointment>;
+    type Item = &'a DoctorsAppointment;
+
+    /// Iterate through the appointments in chronological order
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for DoctorsCalendar {
+    type IntoIter = std::collections::btree_map::IntoValues<AppointmentKey, DoctorsAppointment>;
+    type Item = DoctorsAppointment;
+
+    /// Consume the calendar into its appointments, in chronological order
+    fn into_iter(self) -> Self::IntoIter {
+        Arc::unwrap_or_clone(self.appointments).into_values()
+    }
+}
+
+impl DoctorsCalendar {
+    // Create a new doctor's calendar
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Create a new doctor's calendar that reads the current time from the
+    /// given clock
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            appointments: Arc::new(BTreeMap::new()),
+            archived: vec![],
+            waitlist: Waitlist::new(),
+            blocks: Arc::new(BTreeSet::new()),
+            holds: BTreeMap::new(),
+            next_hold_id: 0,
+            next_appointment_id: 0,
+            occupancy: Arc::new(IntervalIndex::default()),
+            clock,
+            config: Arc::default(),
+            audit_log: None,
+            optimizer_window: Duration::minutes(60),
+            overlap_tolerance: Duration::zero(),
+            version: 0,
+        }
+    }
+
+    /// Create a new doctor's calendar with the given working schedule
+    pub fn with_schedule(schedule: WorkingSchedule) -> Self {
+        Self::with_config(Arc::new(ClinicConfig::new(
+            schedule,
+            AppointmentCatalog::default(),
+        )))
+    }
+
+    /// Create a new doctor's calendar using the configuration of the clinic,
+    /// which can be shared with the calendars of the other doctors
+    pub fn with_config(config: Arc<ClinicConfig>) -> Self {
+        Self {
+            config,
+            ..Self::new()
+        }
+    }
+
+    /// Get the configuration of the clinic used by the calendar
+    pub fn config(&self) -> &Arc<ClinicConfig> {
+        &self.config
+    }
+
+    /// Get the working schedule of the calendar
+    pub fn schedule(&self) -> &WorkingSchedule {
+        &self.config.schedule
+    }
+
+    /// Set the working schedule of the calendar
+    ///
+    /// Existing appointments are kept, even if they are no longer within the
+    /// working hours, see `appointments_outside_schedule`. If the configuration
+    /// is shared, the calendar gets its own copy of it, still sharing the
+    /// closures of the clinic
+    pub fn set_schedule(&mut self, schedule: WorkingSchedule) {
+        Arc::make_mut(&mut self.config).schedule = schedule;
+        self.version += 1;
+    }
+
+    /// Check that the appointments are within the working hours and don't
+    /// overlap, see `validate`
+    pub fn is_consistent(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// Check the integrity of the calendar, e.g. after changing the schedule
+    ///
+    /// Every appointment occupying time must fit in a working hours window and
+    /// must not overlap with another one by more than the overlap tolerance.
+    /// Returns the offending appointments in chronological order, along with
+    /// the reason
+    pub fn validate(&self) -> Result<(), Vec<(DoctorsAppointment, CalendarError)>> {
+        let mut errors = vec![];
+
+        for appointment in self
+            .appointments
+            .values()
+            .filter(|appointment| appointment.status.occupies_time())
+        {
+            if let Err(e) = self
+                .schedule()
+                .end_time_respecting_breaks(appointment.date_time, appointment.duration())
+            {
+                errors.push((appointment.clone(), e));
+                continue;
+            }
+
+            let overlaps = self
+                .occupying_appointments(appointment.date_time, appointment.end_time())
+                .filter(|other| other.key() != appointment.key())
+                .any(|other| overlap_length(other, appointment) > self.overlap_tolerance);
+
+            if overlaps {
+                errors.push((appointment.clone(), CalendarError::Overlap));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Describe the overlapping appointments found by `validate`, one line per
+    /// overlapping pair with their times and the overlap length, e.g.
+    /// "2024-02-01 08:00-08:30 Check-up overlaps 2024-02-01 08:25-08:55
+    /// Check-up by 5 minutes"
+    ///
+    /// Returns an empty string if no appointments overlap
+    pub fn overlap_report(&self) -> String {
+        let Err(errors) = self.validate() else {
+            return String::new();
+        };
+
+        let describe = |appointment: &DoctorsAppointment| {
+            format!(
+                "{}-{} {}",
+                appointment.date_time.format("%Y-%m-%d %H:%M"),
+                appointment.end_time().format("%H:%M"),
+                appointment.appointment_type.display_name()
+            )
+        };
+
+        let mut report = String::new();
+
+        for (appointment, _) in errors.iter().filter(|(_, e)| *e == CalendarError::Overlap) {
+            // Each pair is reported once, by its earlier appointment, or the one
+            // with the lower id if they start at the same time. Identical
+            // appointments are told apart by their ids
+            for other in self
+                .occupying_appointments(appointment.date_time, appointment.end_time())
+                .filter(|other| other.key() > appointment.key())
+                .filter(|other| overlap_length(other, appointment) > self.overlap_tolerance)
+            {
+                report.push_str(&format!(
+                    "{} overlaps {} by {} minutes\n",
+                    describe(appointment),
+                    describe(other),
+                    overlap_length(other, appointment).num_minutes()
+                ));
+            }
+        }
+
+        report
+    }
+
+    /// Extend the working hours of a single date with a one-off window, e.g.
+    /// when the doctor stays late on one day
+    ///
+    /// The rest of the schedule is unchanged, see `WorkingSchedule::extend_day`
+    pub fn extend_day(
+        &mut self,
+        date: NaiveDate,
+        extra_window: (NaiveTime, NaiveTime),
+    ) -> Result<(), ScheduleError> {
+        Arc::make_mut(&mut self.config)
+            .schedule
+            .extend_day(date, extra_window)?;
+        self.version += 1;
+
+        Ok(())
+    }
+
+    /// Get the appointments that don't fit in a working hours window of the
+    /// schedule, e.g. after changing it
+    pub fn appointments_outside_schedule(&self) -> Vec<DoctorsAppointment> {
+        self.appointments
+            .values()
+            .filter(|appointment| appointment.status.occupies_time())
+            .filter(|appointment| {
+                self.schedule()
+                    .end_time_respecting_breaks(appointment.date_time, appointment.duration())
+                    .is_err()
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Get the size of the windows `free_slots_optimized` returns one slot for
+    pub fn optimizer_window(&self) -> Duration {
+        self.optimizer_window
+    }
+
+    /// Set the size of the windows `free_slots_optimized` returns one slot for,
+    /// e.g. 30 minutes for a clinic on a 30 minute cadence. Defaults to 60
+    /// minutes
+    ///
+    /// The size must be a positive multiple of 15 minutes
+    pub fn set_optimizer_window(&mut self, window: Duration) -> Result<(), CalendarError> {
+        if !is_valid_duration(window) {
+            return Err(CalendarError::InvalidDuration);
+        }
+
+        self.optimizer_window = window;
+        self.version += 1;
+
+        Ok(())
+    }
+
+    /// Get how much a new appointment may overlap with an existing one
+    pub fn overlap_tolerance(&self) -> Duration {
+        self.overlap_tolerance
+    }
+
+    /// Set how much a new appointment may overlap with an existing one, e.g.
+    /// 5 minutes for back-to-back cleanings. Defaults to no overlap
+    ///
+    /// Overlaps up to the tolerance are allowed. The tolerance must be shorter
+    /// than a 15 minute time slot
+    pub fn set_overlap_tolerance(&mut self, tolerance: Duration) -> Result<(), CalendarError> {
+        if tolerance < Duration::zero() || tolerance >= Duration::minutes(15) {
+            return Err(CalendarError::InvalidDuration);
+        }
+
+        self.overlap_tolerance = tolerance;
+        self.version += 1;
+
+        Ok(())
+    }
+
+    /// Record the changes made to the calendar from now on in an audit log
+    pub fn with_audit_log(mut self) -> Self {
+        self.audit_log = Some(AuditLog::new());
+        self
+    }
+
+    /// Get the entries of the audit log, from the oldest to the newest
+    ///
+    /// Empty if the audit log is not enabled
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        self.audit_log
+            .as_ref()
+            .map_or(&[], |audit_log| audit_log.entries())
+    }
+
+    /// Record a change in the audit log, if enabled
+    fn record(&mut self, operation: AuditOperation, appointment: &DoctorsAppointment) {
+        let now = self.clock.now();
+
+        if let Some(audit_log) = self.audit_log.as_mut() {
+            audit_log.record(now, operation, appointment.clone());
+        }
+    }
+
+    /// Get the booking rules of the appointment types
+    pub fn catalog(&self) -> &AppointmentCatalog {
+        &self.config.catalog
+    }
+
+    /// Set the booking rules of the appointment types
+    ///
+    /// Existing appointments are kept, even if they no longer respect the
+    /// rules. If the configuration is shared, the calendar gets its own
+    /// copy of it
+    pub fn set_catalog(&mut self, catalog: AppointmentCatalog) {
+        Arc::make_mut(&mut self.config).catalog = catalog;
+        self.version += 1;
+    }
+
+    /// Check if the appointment type can still be booked at the given date and
+    /// time, given its minimum notice
+    fn respects_min_lead(
+        &self,
+        date_time: NaiveDateTime,
+        appointment_type: AppointmentType,
+    ) -> bool {
+        self.catalog()
+            .min_lead(appointment_type)
+            .is_none_or(|min_lead| date_time >= self.clock.now() + min_lead)
+    }
+
+    /// Check if the appointment type is offered from the given date and time
+    /// for the given duration, given its allowed windows
+    fn within_allowed_windows(
+        &self,
+        date_time: NaiveDateTime,
+        appointment_type: AppointmentType,
+        duration: Duration,
+    ) -> bool {
+        self.catalog().is_allowed(
+            appointment_type,
+            date_time.time(),
+            (date_time + duration).time(),
+        )
+    }
+
+    /// Take a read-only snapshot of the calendar, e.g. to answer queries from
+    /// other threads while the calendar keeps changing
+    pub fn snapshot(&self) -> CalendarSnapshot {
+        // Keep the current closures of the clinic, which may be shared with
+        // other calendars
+        CalendarSnapshot {
+            calendar: DoctorsCalendar {
+                config: Arc::new(self.config.frozen()),
+                ..self.clone()
+            },
+        }
+    }
+
+    /// Get the appointments in chronological order
+    pub fn appointments(&self) -> impl Iterator<Item = &DoctorsAppointment> {
+        self.appointments.values()
+    }
+
+    /// Get the number of appointments in the calendar
+    pub fn len(&self) -> usize {
+        self.appointments.len()
+    }
+
+    /// Check if the calendar has no appointments
+    pub fn is_empty(&self) -> bool {
+        self.appointments.is_empty()
+    }
+
+    /// Iterate through the appointments in chronological order
+    pub fn iter(
+        &self,
+    ) -> std::collections::btree_map::Values<'_, AppointmentKey, DoctorsAppointment> {
+        self.appointments.values()
+    }
+
+    /// Resolve the optional `from` and `to` of a query for the given
+    /// appointment type
+    ///
+    /// `from` defaults to the next 15 minute mark from now, and `to` defaults
+    /// to the end of the working day this Friday
+    fn resolve_range(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> (NaiveDateTime, NaiveDateTime) {
+        self.resolve_range_for_duration(from, to, self.catalog().duration(appointment_type))
+    }
+
+    /// Resolve the optional `from` and `to` of a query for an appointment of
+    /// the given duration, see `resolve_range`
+    fn resolve_range_for_duration(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        duration: Duration,
+    ) -> (NaiveDateTime, NaiveDateTime) {
+        // In case `from` is not provided, set it to the current time
+        let from = if let Some(from) = from {
+            from
+        } else {
+            next_15_mark_from(self.clock.now())
+        };
+
+        // In case `to` is not provided, set it to the end of the working day this
+        // Friday
+        let to = if let Some(to) = to {
+            to + duration
+        } else {
+            end_of_week_from(self.clock.now())
+        };
+
+        (from, to)
+    }
+
+    /// Add an appointment to the calendar
+    pub fn add_appointment(
+        &mut self,
+        appointment: DoctorsAppointment,
+    ) -> Result<(), CalendarError> {
+        self.insert_appointment(appointment).map(|_| ())
+    }
+
+    /// Get the version of the calendar, which changes whenever its
+    /// appointments, blocks, holds, schedule, catalog, overlap tolerance or
+    /// optimizer window change, or the closures of its clinic config
+    pub fn version(&self) -> u64 {
+        self.version + self.config.closures_version()
+    }
+
+    /// Add an appointment to the calendar, unless it changed since the client
+    /// read the given version
+    ///
+    /// Fails with `CalendarError::StaleVersion` if another change was made in
+    /// the meantime, so the client can read the calendar again and retry.
+    /// Returns the new version of the calendar
+    pub fn add_appointment_versioned(
+        &mut self,
+        appointment: DoctorsAppointment,
+        expected_version: u64,
+    ) -> Result<u64, CalendarError> {
+        if expected_version != self.version() {
+            return Err(CalendarError::StaleVersion {
+                expected: expected_version,
+                current: self.version(),
+            });
+        }
+
+        self.add_appointment(appointment)?;
+
+        Ok(self.version())
+    }
+
+    /// Add an appointment made of several parts, e.g. a preparation and the
+    /// main appointment with a gap between them
+    ///
+    /// The parts are booked atomically: if any part can't be added, none of
+    /// them is. Returns the ids of the parts, in the given order
+    pub fn add_composite(
+        &mut self,
+        parts: &[(NaiveDateTime, AppointmentType)],
+    ) -> Result<Vec<AppointmentId>, CalendarError> {
+        // Keep the calendar as it was, to roll back if a part doesn't fit
+        let before = self.clone();
+
+        let mut ids = vec![];
+
+        for (date_time, appointment_type) in parts {
+            match self.insert_appointment(DoctorsAppointment::new(*date_time, *appointment_type)) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    *self = before;
+                    return Err(e);
+                },
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Validate and add an appointment to the calendar, returning its id
+    fn insert_appointment(
+        &mut self,
+        appointment: DoctorsAppointment,
+    ) -> Result<AppointmentId, CalendarError> {
+        let appointment = self.resolve_duration(appointment);
+        self.validate_appointment(&appointment)?;
+
+        Ok(self.insert_validated(appointment))
+    }
+
+    /// Store the catalog duration of the appointment type on the appointment,
+    /// unless it has a duration of its own, so later catalog changes don't
+    /// change the booked appointment
+    fn resolve_duration(&self, mut appointment: DoctorsAppointment) -> DoctorsAppointment {
+        let duration = self.catalog().duration(appointment.appointment_type);
+
+        // The default duration of the type never changes, so it's not stored
+        if appointment.duration_override.is_none()
+            && duration != appointment.appointment_type.duration()
+        {
+            appointment.duration_override = Some(duration);
+        }
+
+        appointment
+    }
+
+    /// Store an appointment in the calendar, indexing it if it occupies its
+    /// time slots
+    fn store(&mut self, appointment: DoctorsAppointment) {
+        if appointment.status.occupies_time() {
+            Arc::make_mut(&mut self.occupancy).insert(&appointment);
+        }
+
+        Arc::make_mut(&mut self.appointments).insert(appointment.key(), appointment);
+    }
+
+    /// Remove an appointment from the calendar and its index
+    fn unstore(&mut self, appointment: &DoctorsAppointment) {
+        if appointment.status.occupies_time() {
+            Arc::make_mut(&mut self.occupancy).remove(appointment);
+        }
+
+        Arc::make_mut(&mut self.appointments).remove(&appointment.key());
+    }
+
+    /// Add an already validated appointment to the calendar, returning its id
+    fn insert_validated(&mut self, mut appointment: DoctorsAppointment) -> AppointmentId {
+        // Assign an id, unless the appointment already has one, e.g. when
+        // imported
+        let id = *appointment
+            .id
+            .get_or_insert(AppointmentId(self.next_appointment_id));
+        self.next_appointment_id = self.next_appointment_id.max(id.0 + 1);
+
+        // Same for the creation time
+        appointment.created_at.get_or_insert(self.clock.now());
+        self.record(AuditOperation::Add, &appointment);
+
+        // Add the appointment to the calendar
+        self.store(appointment);
+        self.version += 1;
+
+        id
+    }
+
+    /// Add an appointment to the calendar even if it overlaps with existing
+    /// appointments, e.g. to double-book an emergency
+    ///
+    /// Every other rule, like the working hours, still applies. Returns the
+    /// appointments it overlaps with, which stay in the calendar
+    pub fn force_book(
+        &mut self,
+        appointment: DoctorsAppointment,
+    ) -> Result<Vec<DoctorsAppointment>, CalendarError> {
+        let appointment = self.resolve_duration(appointment);
+        let conflicts = self.conflicts_for(&appointment);
+
+        // The overlap is checked last, so every other rule passed
+        match self.validate_appointment(&appointment) {
+            Ok(()) | Err(CalendarError::Overlap) => {},
+            Err(e) => return Err(e),
+        }
+
+        self.insert_validated(appointment);
+
+        Ok(conflicts)
+    }
+
+    /// Get the existing appointments the proposed appointment would overlap
+    /// with, in chronological order
+    ///
+    /// Unlike `add_appointment`, which stops at the first problem, this lists
+    /// every conflicting appointment. Cancelled and no-show appointments don't
+    /// conflict
+    pub fn conflicts_for(&self, appointment: &DoctorsAppointment) -> Vec<DoctorsAppointment> {
+        self.occupying_appointments(appointment.date_time, appointment.end_time())
+            .cloned()
+            .collect()
+    }
+
+    /// Get the appointment with the given id
+    pub fn appointment(&self, id: AppointmentId) -> Option<&DoctorsAppointment> {
+        self.appointments
+            .values()
+            .find(|appointment| appointment.id == Some(id))
+    }
+
+    /// Add an appointment to the calendar, snapping its start to a free time
+    /// slot
+    ///
+    /// The start is rounded down to the last 15 minute mark. If the
+    /// appointment can't be booked there, it's booked in the next time slot
+    /// where it fits, looking ahead up to 4 weeks. Returns the start of the
+    /// booked appointment, or `CalendarError::NoFreeSlot` if it fits nowhere
+    pub fn add_appointment_snapped(
+        &mut self,
+        mut appointment: DoctorsAppointment,
+    ) -> Result<NaiveDateTime, CalendarError> {
+        // Round down to the last 15 minute time
+        let date_time = appointment.date_time;
+        let mut current = date_time
+            .date()
+            .and_hms_opt(date_time.hour(), (date_time.minute() / 15) * 15, 0)
+            .unwrap();
+        let limit = current + Duration::weeks(4);
+
+        while current < limit {
+            appointment.date_time = current;
+
+            match self.validate_appointment(&appointment) {
+                Ok(()) => {
+                    self.add_appointment(appointment)?;
+
+                    return Ok(current);
+                },
+                // The appointment doesn't fit at any time
+                Err(
+                    e @ (CalendarError::InvalidDuration
+                    | CalendarError::TooLongForAnyWindow(_)
+                    | CalendarError::DuplicateId),
+                ) => return Err(e),
+                Err(_) => current = self.schedule().next_working_datetime(current, None),
+            }
+        }
+
+        Err(CalendarError::NoFreeSlot)
+    }
+
+    /// Get the duration of the appointment type in 15 minute time slots, as set
+    /// in the catalog
+    fn duration_in_time_slots(&self, appointment_type: AppointmentType) -> u8 {
+        (self.catalog().duration(appointment_type).num_minutes() / 15) as u8
+    }
+
+    /// Check that no other appointment in the calendar has the appointment's id
+    fn check_unique_id(&self, appointment: &DoctorsAppointment) -> Result<(), CalendarError> {
+        // Ids are only assigned up from the highest one, so an id above it is
+        // not in use
+        match appointment.id {
+            Some(id) if id.0 < self.next_appointment_id && self.appointment(id).is_some() => {
+                Err(CalendarError::DuplicateId)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Find the appointment starting at the given time, preferring the one
+    /// occupying its time slots over cancelled and no-show ones
+    fn appointment_at(&self, at: NaiveDateTime) -> Result<DoctorsAppointment, CalendarError> {
+        self.appointments_starting_in(at, at + Duration::nanoseconds(1))
+            .max_by_key(|appointment| appointment.status.occupies_time())
+            .cloned()
+            .ok_or(CalendarError::NotFound)
+    }
+
+    /// Check if the appointment can be added to the calendar
+    fn validate_appointment(&self, appointment: &DoctorsAppointment) -> Result<(), CalendarError> {
+        if !is_valid_duration(appointment.duration()) {
+            return Err(CalendarError::InvalidDuration);
+        }
+
+        self.check_unique_id(appointment)?;
+
+        // Reject appointments that can never fit, rather than reporting them as
+        // outside the working hours
+        if appointment.duration() > self.schedule().longest_window() {
+            return Err(CalendarError::TooLongForAnyWindow(appointment.duration()));
+        }
+
+        if !self.respects_min_lead(appointment.date_time, appointment.appointment_type) {
+            return Err(CalendarError::InsufficientNotice);
+        }
+
+        if !self.within_allowed_windows(
+            appointment.date_time,
+            appointment.appointment_type,
+            appointment.duration(),
+        ) {
+            return Err(CalendarError::OutsideAllowedWindow);
+        }
+
+        if appointment
+            .to_reserved_time_slots()
+            .iter()
+            .any(|time_slot| !self.schedule().is_working_day_and_hour(*time_slot))
+        {
+            return Err(CalendarError::OutsideWorkingHours);
+        }
+
+        // Cancelled and no-show appointments don't take their time slots, e.g.
+        // when importing them next to the appointment booked in their place
+        if !appointment.status.occupies_time() {
+            return Ok(());
+        }
+
+        if appointment
+            .to_reserved_time_slots()
+            .iter()
+            .any(|time_slot| self.is_blocked(*time_slot))
+        {
+            return Err(CalendarError::Blocked);
+        }
+
+        if appointment
+            .to_reserved_time_slots()
+            .iter()
+            .any(|time_slot| self.is_held(*time_slot))
+        {
+            return Err(CalendarError::Held);
+        }
+
+        // Check if the appointment overlaps with an existing appointment
+        if self.overlaps_beyond_tolerance(appointment) {
+            return Err(CalendarError::Overlap);
+        }
+
+        Ok(())
+    }
+
+    /// Check if the appointment overlaps with an existing appointment by more
+    /// than the overlap tolerance
+    fn overlaps_beyond_tolerance(&self, appointment: &DoctorsAppointment) -> bool {
+        self.occupying_appointments(appointment.date_time, appointment.end_time())
+            .any(|existing_appointment| {
+                overlap_length(existing_appointment, appointment) > self.overlap_tolerance
+            })
+    }
+
+    /// Hold the time slots of an appointment until the given expiry, without
+    /// booking it
+    ///
+    /// Held time slots are not free, and can't be booked by other
+    /// appointments. The hold is released automatically once the clock passes
+    /// its expiry
+    pub fn hold(
+        &mut self,
+        appointment: DoctorsAppointment,
+        expires_at: NaiveDateTime,
+    ) -> Result<HoldId, CalendarError> {
+        self.release_expired_holds();
+
+        let appointment = self.resolve_duration(appointment);
+        self.validate_appointment(&appointment)?;
+
+        let hold_id = HoldId(self.next_hold_id);
+        self.next_hold_id += 1;
+
+        self.holds.insert(
+            hold_id,
+            Hold {
+                appointment,
+                expires_at,
+            },
+        );
+        self.version += 1;
+
+        Ok(hold_id)
+    }
+
+    /// Book the held appointment
+    ///
+    /// Fails with `CalendarError::HoldNotFound` if the hold was released or
+    /// has expired
+    pub fn confirm(&mut self, hold_id: HoldId) -> Result<(), CalendarError> {
+        self.release_expired_holds();
+
+        let hold = self
+            .holds
+            .remove(&hold_id)
+            .ok_or(CalendarError::HoldNotFound)?;
+
+        // Restore the hold if the appointment can't be booked
+        if let Err(e) = self.add_appointment(hold.appointment.clone()) {
+            self.holds.insert(hold_id, hold);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Release a hold without booking it. Returns whether the hold was active
+    pub fn release(&mut self, hold_id: HoldId) -> bool {
+        self.release_expired_holds();
+
+        let released = self.holds.remove(&hold_id).is_some();
+
+        if released {
+            self.version += 1;
+        }
+
+        released
+    }
+
+    /// Get the active holds in the calendar
+    pub fn holds(&self) -> impl Iterator<Item = (&HoldId, &Hold)> {
+        let now = self.clock.now();
+
+        self.holds
+            .iter()
+            .filter(move |(_, hold)| now < hold.expires_at)
+    }
+
+    /// Remove the holds that have expired
+    fn release_expired_holds(&mut self) {
+        let now = self.clock.now();
+
+        let held = self.holds.len();
+        self.holds.retain(|_, hold| now < hold.expires_at);
+
+        if self.holds.len() < held {
+            self.version += 1;
+        }
+    }
+
+    /// Check if the 15 minute time slot is reserved by an active hold
+    fn is_held(&self, time_slot: NaiveDateTime) -> bool {
+        let now = self.clock.now();
+
+        self.holds.values().any(|hold| hold.covers(time_slot, now))
+    }
+
+    /// Book an appointment of the given type in a free time slot within the
+    /// given time period, placed according to the strategy
+    ///
+    /// Returns the start of the booked appointment, or
+    /// `CalendarError::NoFreeSlot` if the appointment type doesn't fit anywhere
+    pub fn auto_book(
+        &mut self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+        strategy: PlacementStrategy,
+    ) -> Result<NaiveDateTime, CalendarError> {
+        let slot = match strategy {
+            PlacementStrategy::FirstFit => self.free_slots_iter(from, to, appointment_type).next(),
+            PlacementStrategy::BestFit => self
+                .free_slots_optimized(from, to, appointment_type)
+                .first()
+                .copied(),
+        }
+        .ok_or(CalendarError::NoFreeSlot)?;
+
+        self.add_appointment(DoctorsAppointment::new(slot, appointment_type))?;
+
+        Ok(slot)
+    }
+
+    /// Cancel the appointment starting at the given date and time, at the
+    /// patient's request
+    ///
+    /// The appointment is moved to the archive, see `archived_appointments`.
+    /// Returns the cancelled appointment, along with the waitlisted patients
+    /// for that day whose appointment type now fits in the freed time
+    pub fn cancel_appointment(
+        &mut self,
+        date_time: NaiveDateTime,
+    ) -> Result<Cancellation, CalendarError> {
+        self.cancel_appointment_with_reason(date_time, CancellationReason::PatientRequest)
+    }
+
+    /// Same as `cancel_appointment`, recording why the appointment was
+    /// cancelled in the archive
+    pub fn cancel_appointment_with_reason(
+        &mut self,
+        date_time: NaiveDateTime,
+        reason: CancellationReason,
+    ) -> Result<Cancellation, CalendarError> {
+        let appointment = self.appointment_at(date_time)?;
+
+        // Move the appointment from the calendar to the archive
+        self.archive(&appointment, reason);
+
+        let start = appointment.date_time;
+        let end = appointment.end_time();
+        let from = start.date().and_time(NaiveTime::MIN);
+        let to = from + Duration::days(1) - Duration::seconds(1);
+
+        // Find the waitlisted patients that can use the freed time
+        let waitlisted = self
+            .waitlist
+            .for_date(start.date())
+            .into_iter()
+            .filter(|(appointment_type, _)| {
+                self.free_slots(Some(from), Some(to), *appointment_type)
+                    .iter()
+                    .any(|slot| {
+                        *slot < end && *slot + self.catalog().duration(*appointment_type) > start
+                    })
+            })
+            .map(|(appointment_type, patient)| (appointment_type, patient.clone()))
+            .collect();
+
+        Ok(Cancellation {
+            appointment,
+            waitlisted,
+        })
+    }
+
+    /// Change the type of the appointment starting at the given date and time
+    ///
+    /// The appointment keeps its start time and patient, and is validated
+    /// again with its new duration against the working hours, the blocks and
+    /// the other appointments. If the new type doesn't fit, the original
+    /// appointment remains unchanged
+    pub fn change_type(
+        &mut self,
+        at: NaiveDateTime,
+        new_type: AppointmentType,
+    ) -> Result<(), CalendarError> {
+        let appointment = self.appointment_at(at)?;
+
+        let changed_appointment = self.resolve_duration(DoctorsAppointment {
+            appointment_type: new_type,
+            duration_override: None,
+            ..appointment.clone()
+        });
+
+        // Remove the appointment so it doesn't overlap with itself
+        self.unstore(&appointment);
+
+        // Restore the original appointment if the new type doesn't fit
+        if let Err(e) = self.validate_appointment(&changed_appointment) {
+            self.store(appointment);
+            return Err(e);
+        }
+
+        self.record(AuditOperation::ChangeType, &changed_appointment);
+        self.store(changed_appointment);
+        self.version += 1;
+
+        Ok(())
+    }
+
+    /// Move every appointment starting within the given time period by the
+    /// offset, e.g. when the morning runs late
+    ///
+    /// The moved appointments are validated again, against the working hours
+    /// and the other appointments, including each other. If any of them
+    /// doesn't fit, none is moved
+    pub fn shift_range(
+        &mut self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        offset: Duration,
+    ) -> Result<(), CalendarError> {
+        let appointments = self.booked_appointments(Some(from), Some(to));
+
+        // Keep the calendar as it was, to roll back if an appointment doesn't fit
+        let before = self.clone();
+
+        // Remove all the appointments first, so they don't overlap with their
+        // old time slots
+        for appointment in appointments.iter() {
+            self.unstore(appointment);
+        }
+
+        let mut shifted_appointments = vec![];
+
+        for appointment in appointments {
+            let shifted_appointment = DoctorsAppointment {
+                date_time: appointment.date_time + offset,
+                ..appointment
+            };
+
+            if shifted_appointment.status.occupies_time() {
+                if let Err(e) = self.validate_appointment(&shifted_appointment) {
+                    *self = before;
+                    return Err(e);
+                }
+            }
+
+            self.store(shifted_appointment.clone());
+            shifted_appointments.push(shifted_appointment);
+        }
+
+        self.version += 1;
+
+        // Only record the moves once all of them succeeded
+        for shifted_appointment in shifted_appointments.iter() {
+            self.record(AuditOperation::Reschedule, shifted_appointment);
+        }
+
+        Ok(())
+    }
+
+    /// Set the status of the appointment starting at the given date and time
+    ///
+    /// Cancelled and no-show appointments stay in the calendar, but free their
+    /// time slots. Setting them back to scheduled or completed fails if their
+    /// time slots were booked in the meantime
+    pub fn set_status(
+        &mut self,
+        at: NaiveDateTime,
+        status: AppointmentStatus,
+    ) -> Result<(), CalendarError> {
+        let appointment = self.appointment_at(at)?;
+
+        let changed_appointment = DoctorsAppointment {
+            status,
+            ..appointment.clone()
+        };
+
+        // Check that the time slots are still free when reserving them again
+        if status.occupies_time()
+            && !appointment.status.occupies_time()
+            && self.overlaps_beyond_tolerance(&changed_appointment)
+        {
+            return Err(CalendarError::Overlap);
+        }
+
+        self.unstore(&appointment);
+        self.record(AuditOperation::StatusChange, &changed_appointment);
+        self.store(changed_appointment);
+        self.version += 1;
+
+        Ok(())
+    }
+
+    /// Cancel every appointment starting within the given time period
+    ///
+    /// The appointments are moved to the archive, like with
+    /// `cancel_appointment`, as cancelled for a clinic closure. Returns the
+    /// cancelled appointments in chronological order, so they can be
+    /// rescheduled or the patients notified
+    pub fn cancel_range(
+        &mut self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<DoctorsAppointment> {
+        let cancelled_appointments = self.booked_appointments(Some(from), Some(to));
+
+        for appointment in cancelled_appointments.iter() {
+            self.archive(appointment, CancellationReason::ClinicClosure);
+        }
+
+        cancelled_appointments
+    }
+
+    /// Move a cancelled appointment from the calendar to the archive
+    fn archive(&mut self, appointment: &DoctorsAppointment, reason: CancellationReason) {
+        self.unstore(appointment);
+        self.record(AuditOperation::Cancel, appointment);
+        self.archived.push((appointment.clone(), reason));
+        self.version += 1;
+    }
+
+    /// Get the archived appointments starting within the given time period, in
+    /// chronological order
+    ///
+    /// Each appointment is returned as it was when archived, along with its
+    /// archived status, i.e. a no-show or cancelled. Archived appointments no
+    /// longer reserve time slots and are not part of `booked_appointments`
+    pub fn archived_appointments(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> Vec<(DoctorsAppointment, AppointmentStatus)> {
+        let mut archived_appointments: Vec<(DoctorsAppointment, AppointmentStatus)> = self
+            .archived_within(from, to)
+            .map(|(appointment, reason)| (appointment.clone(), reason.status()))
+            .collect();
+
+        archived_appointments.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        archived_appointments
+    }
+
+    /// Count the archived appointments starting within the given time period
+    /// per cancellation reason
+    pub fn cancellations_by_reason(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> BTreeMap<CancellationReason, usize> {
+        let mut cancellations: BTreeMap<CancellationReason, usize> = BTreeMap::new();
+
+        for (_, reason) in self.archived_within(from, to) {
+            *cancellations.entry(*reason).or_default() += 1;
+        }
+
+        cancellations
+    }
+
+    /// Count the no-shows starting within the given time period, whether they
+    /// are still in the calendar or archived
+    pub fn no_show_count(&self, from: Option<NaiveDateTime>, to: Option<NaiveDateTime>) -> usize {
+        let booked = self
+            .booked_appointments(from, to)
+            .iter()
+            .filter(|appointment| appointment.status == AppointmentStatus::NoShow)
+            .count();
+        let archived = self
+            .archived_within(from, to)
+            .filter(|(_, reason)| reason.status() == AppointmentStatus::NoShow)
+            .count();
+
+        booked + archived
+    }
+
+    /// Get the archived appointments starting within the given time period, in
+    /// the order they were archived
+    fn archived_within(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> impl Iterator<Item = &(DoctorsAppointment, CancellationReason)> {
+        self.archived.iter().filter(move |(appointment, _)| {
+            from.is_none_or(|from| appointment.date_time >= from)
+                && to.is_none_or(|to| appointment.date_time <= to)
+        })
+    }
+
+    /// Compare the appointments of this calendar ("before") with another one
+    /// ("after")
+    ///
+    /// Appointments that changed in any way, e.g. their patient, are reported
+    /// as removed and added again
+    pub fn diff(&self, other: &DoctorsCalendar) -> CalendarDiff {
+        // Appointments are compared by value, whatever their id
+        let missing_from = |calendar: &DoctorsCalendar, appointment: &DoctorsAppointment| {
+            !calendar
+                .appointments_starting_in(
+                    appointment.date_time,
+                    appointment.date_time + Duration::nanoseconds(1),
+                )
+                .any(|other| other == appointment)
+        };
+
+        CalendarDiff {
+            added: other
+                .appointments()
+                .filter(|appointment| missing_from(self, appointment))
+                .cloned()
+                .collect(),
+            removed: self
+                .appointments()
+                .filter(|appointment| missing_from(other, appointment))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Export the appointments as JSON Lines, one appointment per line in
+    /// chronological order
+    ///
+    /// Each appointment is written as it's serialized, so the whole calendar is
+    /// never held in memory as a string. Appointments are written with the
+    /// current schema version, see `APPOINTMENT_SCHEMA_VERSION`
+    pub fn export_jsonl(&self, mut writer: impl Write) -> io::Result<()> {
+        for appointment in self.appointments.values() {
+            serde_json::to_writer(
+                &mut writer,
+                &VersionedAppointment {
+                    schema_version: APPOINTMENT_SCHEMA_VERSION,
+                    appointment,
+                },
+            )?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()
+    }
+
+    /// Import appointments from JSON Lines, one appointment per line
+    ///
+    /// Appointments written with an older schema version get the defaults of
+    /// the fields they miss, and newer schema versions are rejected. Durations
+    /// that are not a multiple of 15 minutes are rounded according
+    /// to the rounding policy. Every appointment is then added through
+    /// `force_book`, so it's validated against the working hours, but may
+    /// overlap with the existing appointments, e.g. to restore a double
+    /// booking. Appointments keep their ids, and an id already
+    /// in use fails with `CalendarError::DuplicateId`. Empty lines are
+    /// skipped. The import stops at the first invalid line, keeping the
+    /// appointments imported before it. Returns the number of imported
+    /// appointments
+    pub fn import_jsonl(
+        &mut self,
+        reader: impl BufRead,
+        rounding_policy: RoundingPolicy,
+    ) -> io::Result<usize> {
+        self.read_jsonl(reader, rounding_policy, |calendar, appointment| {
+            calendar.force_book(appointment).map(|_| ())
+        })
+    }
+
+    /// Restore appointments saved with `export_jsonl`, e.g. when loading a
+    /// calendar file
+    ///
+    /// Unlike `import_jsonl`, the appointments are kept as they were saved,
+    /// even outside the working hours, e.g. after the schedule changed. See
+    /// `appointments_outside_schedule` for those. Only invalid durations and
+    /// ids already in use are rejected. Returns the number of restored
+    /// appointments
+    pub fn restore_jsonl(&mut self, reader: impl BufRead) -> io::Result<usize> {
+        self.read_jsonl(reader, RoundingPolicy::Reject, |calendar, appointment| {
+            let appointment = calendar.resolve_duration(appointment);
+
+            if !is_valid_duration(appointment.duration()) {
+                return Err(CalendarError::InvalidDuration);
+            }
+            calendar.check_unique_id(&appointment)?;
+
+            calendar.insert_validated(appointment);
+
+            Ok(())
+        })
+    }
+
+    /// Read appointments from JSON Lines, see `import_jsonl`, adding each one
+    /// with the given function
+    fn read_jsonl(
+        &mut self,
+        reader: impl BufRead,
+        rounding_policy: RoundingPolicy,
+        mut add: impl FnMut(&mut Self, DoctorsAppointment) -> Result<(), CalendarError>,
+    ) -> io::Result<usize> {
+        let mut imported = 0;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut appointment = migrate_appointment(serde_json::from_str(&line)?)?;
+            appointment.duration_override = appointment
+                .duration_override
+                .map(|duration| rounding_policy.apply(duration));
+
+            add(self, appointment).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", index + 1, e),
+                )
+            })?;
+
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+
+    /// Block time in the calendar
+    pub fn add_block(&mut self, block: Block) {
+        Arc::make_mut(&mut self.blocks).insert(block);
+        self.version += 1;
+    }
+
+    /// Close the practice on the given date, for the whole day or only the
+    /// given time window, e.g. an afternoon off
+    ///
+    /// The closure is added as a block, which is returned so the closure can be
+    /// lifted with `remove_block`
+    pub fn add_closure(
+        &mut self,
+        date: NaiveDate,
+        window: Option<(NaiveTime, NaiveTime)>,
+    ) -> Block {
+        let block = Block::closure(date, window);
+        self.add_block(block.clone());
+
+        block
+    }
+
+    /// Block the rest of the working day from the given date and time, e.g.
+    /// when the doctor leaves early
+    ///
+    /// Returns the appointments overlapping with the blocked time, in
+    /// chronological order, so they can be rescheduled. They are kept in the
+    /// calendar. Nothing is blocked after the end of the working day
+    pub fn block_rest_of_day(&mut self, from: NaiveDateTime) -> Vec<DoctorsAppointment> {
+        let Some(end) = self
+            .schedule()
+            .windows_on(from.date())
+            .last()
+            .map(|(_, end)| from.date().and_time(*end))
+            .filter(|end| *end > from)
+        else {
+            return vec![];
+        };
+
+        self.add_block(Block::new(from, end, "Rest of day"));
+
+        self.occupying_appointments(from, end).cloned().collect()
+    }
+
+    /// Remove a block from the calendar. Returns whether the block existed
+    pub fn remove_block(&mut self, block: &Block) -> bool {
+        let removed = Arc::make_mut(&mut self.blocks).remove(block);
+
+        if removed {
+            self.version += 1;
+        }
+
+        removed
+    }
+
+    /// Get the blocks in the calendar
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.iter()
+    }
+
+    /// Check if the 15 minute time slot is covered by any block
+    fn is_blocked(&self, time_slot: NaiveDateTime) -> bool {
+        self.blocks.iter().any(|block| block.covers(time_slot)) || self.config.is_closed(time_slot)
+    }
+
+    /// Get the list of booked appointments
+    pub fn booked_appointments(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> Vec<DoctorsAppointment> {
+        // Get the list of booked appointments
+        let booked_appointments = self
+            .appointments
+            .values()
+            .filter(|appointment| {
+                if let Some(from) = from {
+                    if appointment.date_time < from {
+                        return false;
+                    }
+                }
+
+                if let Some(to) = to {
+                    if appointment.date_time > to {
+                        return false;
+                    }
+                }
+
+                true
+            })
+            .cloned()
+            .collect::<Vec<DoctorsAppointment>>();
+
+        booked_appointments
+    }
+
+    /// Count the booked appointments of each appointment type from now until
+    /// the end of the working week
+    ///
+    /// Uses the same default time period as `free_slots` without a range, read
+    /// from the calendar's clock. Every appointment type is listed, even
+    /// without appointments. Cancelled and no-show appointments are not counted
+    pub fn this_week_summary(&self) -> BTreeMap<AppointmentType, usize> {
+        let (from, to) = self.resolve_range(None, None, AppointmentType::UrgentDentalAppointment);
+
+        let mut summary: BTreeMap<AppointmentType, usize> = AppointmentType::all_by_duration_desc()
+            .map(|appointment_type| (appointment_type, 0))
+            .collect();
+
+        for appointment in self
+            .booked_appointments(Some(from), Some(to))
+            .iter()
+            .filter(|appointment| appointment.status.occupies_time())
+        {
+            *summary.entry(appointment.appointment_type).or_default() += 1;
+        }
+
+        summary
+    }
+
+    /// Get the booked appointments grouped by appointment type, e.g. for
+    /// printing worklists
+    ///
+    /// The groups follow the order of the appointment types, from the longest
+    /// to the shortest duration, and each group is in chronological order
+    pub fn appointments_grouped_by_type(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> BTreeMap<AppointmentType, Vec<DoctorsAppointment>> {
+        let mut groups: BTreeMap<AppointmentType, Vec<DoctorsAppointment>> = BTreeMap::new();
+
+        for appointment in self.booked_appointments(from, to) {
+            groups
+                .entry(appointment.appointment_type)
+                .or_default()
+                .push(appointment);
+        }
+
+        groups
+    }
+
+    /// Get the appointments immediately before and after the given date and
+    /// time
+    ///
+    /// The first one is the last appointment starting before `at`, and the
+    /// second one is the first appointment starting at or after `at`
+    pub fn neighbors(
+        &self,
+        at: NaiveDateTime,
+    ) -> (Option<DoctorsAppointment>, Option<DoctorsAppointment>) {
+        // Appointments are keyed on their date and time first, and this is the
+        // smallest key starting at `at`
+        let bound = (at, None);
+
+        let before = self
+            .appointments
+            .range(..bound)
+            .next_back()
+            .map(|(_, appointment)| appointment.clone());
+        let after = self
+            .appointments
+            .range(bound..)
+            .next()
+            .map(|(_, appointment)| appointment.clone());
+
+        (before, after)
+    }
+
+    /// Get the appointments of the week containing the given day
+    ///
+    /// The week runs from Monday 00:00 up to the next Monday 00:00. Unlike
+    /// `booked_appointments`, this only visits the appointments of that week
+    pub fn week(&self, any_day_in_week: NaiveDate) -> Vec<DoctorsAppointment> {
+        let monday = any_day_in_week
+            - Duration::days(any_day_in_week.weekday().num_days_from_monday() as i64);
+        let start = monday.and_time(NaiveTime::MIN);
+
+        self.appointments_starting_in(start, start + Duration::weeks(1))
+            .cloned()
+            .collect()
+    }
+
+    /// Get the scheduled appointments that need a reminder sent now
+    ///
+    /// These are the appointments starting within a 15 minute time slot,
+    /// `lead` after `now`, so running this every 15 minutes reminds each
+    /// appointment once. Completed, missed or cancelled appointments are not
+    /// reminded
+    pub fn due_reminders(&self, now: NaiveDateTime, lead: Duration) -> Vec<DoctorsAppointment> {
+        let start = now + lead;
+
+        self.appointments_starting_in(start, start + Duration::minutes(15))
+            .filter(|appointment| appointment.status == AppointmentStatus::Scheduled)
+            .cloned()
+            .collect()
+    }
+
+    /// Get the busy time within the given time period, as sorted,
+    /// non-overlapping `(start, end)` intervals
+    ///
+    /// Back-to-back appointments are merged into a single interval, and the
+    /// intervals are clipped to the time period
+    pub fn busy_intervals(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        // The appointments are sorted by their start
+        merge_intervals(self.occupying_appointments(from, to).map(|appointment| {
+            (
+                appointment.date_time.max(from),
+                appointment.end_time().min(to),
+            )
+        }))
+    }
+
+    /// Count the 15 minute time slots reserved by the booked appointments
+    /// within the given time period
+    ///
+    /// Unlike the number of booked appointments, this weighs every appointment
+    /// by its duration
+    pub fn booked_slot_count(&self, from: NaiveDateTime, to: NaiveDateTime) -> usize {
+        self.booked_appointments(Some(from), Some(to))
+            .iter()
+            .filter(|appointment| appointment.status.occupies_time())
+            .map(|appointment| appointment.to_reserved_time_slots().len())
+            .sum()
+    }
+
+    /// Get the list of booked appointments with the given tag
+    pub fn filter_by_tag(
+        &self,
+        tag: &str,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> Vec<DoctorsAppointment> {
+        self.booked_appointments(from, to)
+            .into_iter()
+            .filter(|appointment| appointment.tags.contains(tag))
+            .collect()
+    }
+
+    /// Find the appointments of a patient
+    ///
+    /// The query matches case-insensitively anywhere in the patient's name, or
+    /// exactly the patient's phone. Results are in chronological order
+    pub fn find_by_patient(&self, query: &str) -> Vec<DoctorsAppointment> {
+        let query = query.trim();
+
+        // An empty query would match every patient's name
+        if query.is_empty() {
+            return vec![];
+        }
+
+        let query_lowercase = query.to_lowercase();
+
+        // The appointments are already sorted by date and time
+        self.appointments
+            .values()
+            .filter(|appointment| {
+                appointment.patient.as_ref().is_some_and(|patient| {
+                    patient.name.to_lowercase().contains(&query_lowercase) || patient.phone == query
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Fill the calendar with random appointments of the given type.
+    ///
+    /// The appointments will try to be filled up to the given percentage. If
+    /// the percentage can't be reached because there are no more free slots for
+    /// that appointment type, the function will stop.
+    /// Existing appointments will be counted towards the percentage.
+    /// Percentages above 100 are treated as 100.
+    pub fn fill_random(
+        &mut self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        appointment_type: AppointmentType,
+        filled_percentage: u8,
+    ) {
+        // The calendar can't be filled more than completely
+        let filled_percentage = filled_percentage.min(100);
+
+        // Count the total time spots within the given time period
+        let total_time_spots = self.schedule().working_slots(from, to).count();
+
+        loop {
+            // Get list of free slots for the given time period and appointment type
+            let free_slots = self.free_slots(Some(from), Some(to), appointment_type);
+
+            // If there are no free slots, break the loop
+            if free_slots.is_empty() {
+                break;
+            }
+
+            // Get random free slot from list to fill
+            let random_index = rand::thread_rng().gen_range(0..free_slots.len());
+            let random_slot = free_slots[random_index];
+
+            // Create a new appointment for the random slot
+            let appointment = DoctorsAppointment::new(random_slot, appointment_type);
+
+            // Add the appointment to the calendar
+            self.add_appointment(appointment).unwrap();
+
+            // Get list of booked appointments
+            let booked_appointments = self.booked_appointments(Some(from), Some(to));
+
+            // Convert the booked appointments to reserved time slots
+            let reserved_time_slots = booked_appointments
+                .iter()
+                .filter(|appointment| appointment.status.occupies_time())
+                .flat_map(|appointment| appointment.to_reserved_time_slots())
+                .collect::<Vec<NaiveDateTime>>();
+
+            // Check if the calendar is filled as much as possible up to the given
+            // percentage
+            if reserved_time_slots.len() as f64 / total_time_spots as f64 * 100.0
+                == filled_percentage as f64
+                || (reserved_time_slots.len()
+                    + self.duration_in_time_slots(appointment_type) as usize)
+                    as f64
+                    / total_time_spots as f64
+                    * 100.0
+                    > filled_percentage as f64
+            {
+                break;
+            }
+        }
+    }
+
+    /// Fill the calendar with random appointments, up to a target percentage
+    /// of the working time per appointment type
+    ///
+    /// Like a real booking pattern, the long-duration appointments are placed
+    /// first, since they are the hardest to fit, and the shorter ones fill the
+    /// gaps. Every appointment is placed in a random time slot chosen by
+    /// `free_slots_optimized`. Existing appointments count towards the
+    /// percentage of their type
+    pub fn fill_realistic(
+        &mut self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        targets: &BTreeMap<AppointmentType, u8>,
+    ) {
+        let total_time_slots = self.total_working_slots(from, to);
+
+        for appointment_type in AppointmentType::all_by_duration_desc() {
+            let Some(target) = targets.get(&appointment_type) else {
+                continue;
+            };
+
+            // The calendar can't be filled more than completely
+            let target_time_slots = total_time_slots * (*target).min(100) as usize / 100;
+            let type_time_slots = self.duration_in_time_slots(appointment_type) as usize;
+
+            // Count the time slots already reserved by the appointment type
+            let mut reserved_time_slots: usize = self
+                .booked_appointments(Some(from), Some(to))
+                .iter()
+                .filter(|appointment| {
+                    appointment.status.occupies_time()
+                        && appointment.appointment_type == appointment_type
+                })
+                .map(|appointment| appointment.to_reserved_time_slots().len())
+                .sum();
+
+            while reserved_time_slots + type_time_slots <= target_time_slots {
+                let free_slots = self.free_slots_optimized(Some(from), Some(to), appointment_type);
+
+                if free_slots.is_empty() {
+                    break;
+                }
+
+                // Get random free slot from list to fill
+                let random_index = rand::thread_rng().gen_range(0..free_slots.len());
+                let appointment =
+                    DoctorsAppointment::new(free_slots[random_index], appointment_type);
+
+                if self.add_appointment(appointment).is_err() {
+                    break;
+                }
+
+                reserved_time_slots += type_time_slots;
+            }
+        }
+    }
+
+    /// Get list of available 15 minute time slots for the given time period
+    pub fn available_single_time_slots(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<NaiveDateTime> {
+        // Get list of existing appointments within the given time period
+        let existing_appointments = self.booked_appointments(Some(from), Some(to));
+
+        // Convert the booked appointments to reserved time slots
+        let reserved_time_slots: Vec<NaiveDateTime> = existing_appointments
+            .iter()
+            .filter(|appointment| appointment.status.occupies_time())
+            .flat_map(|appointment| appointment.to_reserved_time_slots())
+            .collect();
+
+        // Create the list of available time slots
+        let mut available_time_slots = vec![];
+
+        for current in self.schedule().working_slots(from, to) {
+            // Check if the current time has already been reserved, blocked or held
+            if !reserved_time_slots.contains(&current)
+                && !self.is_blocked(current)
+                && !self.is_held(current)
+            {
+                // Add the current time to the list of available time slots
+                available_time_slots.push(current);
+            }
+        }
+
+        available_time_slots
+    }
+
+    /// Get the free and busy working time for the given time period as a JSON
+    /// free/busy document
+    ///
+    /// The document is an object with `busy` and `free` lists of `{start,
+    /// end}` intervals, which together cover the working hours of the time
+    /// period. The busy time is the `busy_intervals` of the appointments plus
+    /// the blocked and held time slots. The timestamps are RFC 3339, with the
+    /// given UTC offset of the practice, e.g. `+01:00` for Central European
+    /// Time, so that consumers in other timezones place them correctly
+    pub fn freebusy_json(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        offset: FixedOffset,
+    ) -> String {
+        let slot_interval = |slot: NaiveDateTime| (slot, slot + Duration::minutes(15));
+        let to_intervals = |intervals: Vec<(NaiveDateTime, NaiveDateTime)>| {
+            intervals
+                .into_iter()
+                .map(|(start, end)| FreeBusyInterval::new(start, end, offset))
+                .collect()
+        };
+
+        let mut busy = self.busy_intervals(from, to);
+        busy.extend(self.reserved_slots(from, to).into_iter().map(slot_interval));
+        busy.sort();
+
+        let free_busy = FreeBusy {
+            busy: to_intervals(merge_intervals(busy)),
+            free: to_intervals(merge_intervals(
+                self.available_single_time_slots(from, to)
+                    .into_iter()
+                    .map(slot_interval),
+            )),
+        };
+
+        serde_json::to_string(&free_busy).unwrap()
+    }
+
+    /// Get the busy 15 minute time slots for the given time period
+    ///
+    /// These are the working time slots reserved by appointments, blocked or
+    /// held, i.e. the complement of `available_single_time_slots`
+    pub fn reserved_slots(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> BTreeSet<NaiveDateTime> {
+        let available_time_slots: BTreeSet<NaiveDateTime> = self
+            .available_single_time_slots(from, to)
+            .into_iter()
+            .collect();
+
+        self.schedule()
+            .working_slots(from, to)
+            .filter(|current| !available_time_slots.contains(current))
+            .collect()
+    }
+
+    /// Count the working 15 minute time slots within the given time period
+    pub fn total_working_slots(&self, from: NaiveDateTime, to: NaiveDateTime) -> usize {
+        self.schedule().working_slots(from, to).count()
+    }
+
+    /// Get the percentage of working time slots within the given time period
+    /// that are reserved by appointments
+    pub fn utilization(&self, from: NaiveDateTime, to: NaiveDateTime) -> f64 {
+        let total_time_slots = self.total_working_slots(from, to);
+
+        if total_time_slots == 0 {
+            return 0.0;
+        }
+
+        // Count the reserved time slots within the given time period
+        let reserved_time_slots = self
+            .booked_appointments(Some(from), Some(to))
+            .iter()
+            .filter(|appointment| appointment.status.occupies_time())
+            .flat_map(|appointment| appointment.to_reserved_time_slots())
+            .filter(|time_slot| {
+                *time_slot < to && self.schedule().is_working_day_and_hour(*time_slot)
+            })
+            .count();
+
+        reserved_time_slots as f64 / total_time_slots as f64 * 100.0
+    }
+
+    /// Get the working day with the lowest utilization between the given
+    /// dates, both included
+    ///
+    /// Ties go to the earliest day. Returns `None` if there is no working day
+    /// between the dates
+    pub fn least_busy_day(&self, from: NaiveDate, to: NaiveDate) -> Option<NaiveDate> {
+        from.iter_days()
+            .take_while(|date| *date <= to)
+            .filter(|date| self.schedule().is_working_day(*date))
+            .map(|date| {
+                let start = date.and_time(NaiveTime::MIN);
+
+                (date, self.utilization(start, start + Duration::days(1)))
+            })
+            // Keep the first of the least busy days
+            .fold(
+                None,
+                |least_busy: Option<(NaiveDate, f64)>, (date, utilization)| match least_busy {
+                    Some((_, least_utilization)) if least_utilization <= utilization => least_busy,
+                    _ => Some((date, utilization)),
+                },
+            )
+            .map(|(date, _)| date)
+    }
+
+    /// Sum the free working time within the given time period
+    ///
+    /// This is the time left in the working hours, i.e. not reserved by
+    /// appointments, blocked or held
+    pub fn idle_time(&self, from: NaiveDateTime, to: NaiveDateTime) -> Duration {
+        Duration::minutes(15) * self.available_single_time_slots(from, to).len() as i32
+    }
+
+    /// Count how far in advance the appointments were booked
+    ///
+    /// The buckets are the ascending upper bounds of the lead times, e.g. 1
+    /// day, 1 week and 4 weeks. The count at index `i` is the number of
+    /// appointments booked less than `buckets[i]` but at least
+    /// `buckets[i - 1]` in advance, and the extra last count is for the
+    /// appointments booked at least the last bound in advance. Appointments
+    /// without a creation time are not counted
+    pub fn lead_time_histogram(&self, buckets: &[Duration]) -> Vec<usize> {
+        let mut histogram = vec![0; buckets.len() + 1];
+
+        for appointment in self.appointments.values() {
+            let Some(created_at) = appointment.created_at else {
+                continue;
+            };
+
+            let lead_time = appointment.date_time - created_at;
+            let bucket = buckets.partition_point(|bound| *bound <= lead_time);
+
+            histogram[bucket] += 1;
+        }
+
+        histogram
+    }
+
+    /// Calculate the percentage of no-shows among the appointments that took
+    /// place or were missed within the given time period
+    ///
+    /// No-shows are counted like `no_show_count`, whether they are still in
+    /// the calendar or archived. Returns 0.0 if no appointment is completed or
+    /// missed
+    pub fn no_show_rate(&self, from: NaiveDateTime, to: NaiveDateTime) -> f64 {
+        let completed = self
+            .booked_appointments(Some(from), Some(to))
+            .iter()
+            .filter(|appointment| appointment.status == AppointmentStatus::Completed)
+            .count();
+        let no_shows = self.no_show_count(Some(from), Some(to));
+
+        if completed + no_shows == 0 {
+            return 0.0;
+        }
+
+        no_shows as f64 / (completed + no_shows) as f64 * 100.0
+    }
+
+    /// Get the number of reserved and total working 15 minute time slots per
+    /// ISO week within the given time period
+    ///
+    /// The keys are the ISO year and week number, and the values the number of
+    /// time slots reserved by appointments and the number of working time slots
+    pub fn weekly_utilization(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> BTreeMap<(i32, u32), (usize, usize)> {
+        let mut weeks: BTreeMap<(i32, u32), (usize, usize)> = BTreeMap::new();
+
+        for current in self.schedule().working_slots(from, to) {
+            let iso_week = current.iso_week();
+            let (booked, total) = weeks.entry((iso_week.year(), iso_week.week())).or_default();
+
+            *total += 1;
+
+            if self.is_reserved(current) {
+                *booked += 1;
+            }
+        }
+
+        weeks
+    }
+
+    /// Count the used and working 15 minute time slots of every window within
+    /// the given time period
+    ///
+    /// The windows are grouped like `free_slots_optimized` does, flooring the
+    /// time of the day to the window size. The values are the time slots
+    /// reserved by appointments, blocked or held, and the working time slots
+    /// of the window. Windows without working time are left out, and so is
+    /// everything when the window is shorter than a minute
+    pub fn window_density(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        window: Duration,
+    ) -> BTreeMap<NaiveDateTime, (usize, usize)> {
+        let mut windows: BTreeMap<NaiveDateTime, (usize, usize)> = BTreeMap::new();
+
+        let window_minutes = window.num_minutes();
+
+        if window_minutes < 1 {
+            return windows;
+        }
+
+        let reserved_time_slots = self.reserved_slots(from, to);
+
+        for time_slot in self.schedule().working_slots(from, to) {
+            let (used, capacity) = windows
+                .entry(window_start(time_slot, window_minutes))
+                .or_default();
+
+            *capacity += 1;
+
+            if reserved_time_slots.contains(&time_slot) {
+                *used += 1;
+            }
+        }
+
+        windows
+    }
+
+    /// Get the runs of consecutive free 15 minute time slots within the given
+    /// time period
+    ///
+    /// Returns the start of each run and its number of time slots. Runs never
+    /// cross the break or span multiple days
+    pub fn free_run_lengths(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<(NaiveDateTime, usize)> {
+        let mut runs: Vec<(NaiveDateTime, usize)> = vec![];
+        let mut previous: Option<NaiveDateTime> = None;
+
+        for time_slot in self.available_single_time_slots(from, to) {
+            match (previous, runs.last_mut()) {
+                // Extend the current run if the time slot is directly connected
+                (Some(previous), Some((_, length))) if are_connected(previous, time_slot) => {
+                    *length += 1;
+                },
+                // Otherwise start a new run
+                _ => runs.push((time_slot, 1)),
+            }
+
+            previous = Some(time_slot);
+        }
+
+        runs
+    }
+
+    /// Get the longest free window of a day, as its start and length
+    ///
+    /// Free windows are built from the runs of free time slots, so they never
+    /// cross a break. On ties the earliest window is returned. Returns `None`
+    /// if the day has no free time
+    pub fn longest_free_window(&self, date: NaiveDate) -> Option<(NaiveDateTime, Duration)> {
+        let from = date.and_time(NaiveTime::MIN);
+        let to = from + Duration::days(1);
+
+        self.free_run_lengths(from, to)
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, length)| *length)
+            .map(|(start, length)| (start, Duration::minutes(15) * length as i32))
+    }
+
+    /// Propose new start times for the appointments of a day, packing them
+    /// from the start of the working day without idle gaps
+    ///
+    /// The appointments keep their order. An appointment that doesn't fit
+    /// before the break moves after it, along with all the following ones, and
+    /// blocked time is skipped. Appointments that don't fit anywhere keep
+    /// their current start. The calendar isn't changed
+    pub fn compact_day_plan(&self, date: NaiveDate) -> Vec<(DoctorsAppointment, NaiveDateTime)> {
+        let day_time_slots = self.schedule().day_time_slots(date);
+
+        let Some(mut current) = day_time_slots.first().copied() else {
+            return vec![];
+        };
+
+        let from = date.and_time(NaiveTime::MIN);
+        let to = from + Duration::days(1);
+
+        let mut plan = vec![];
+
+        for appointment in self
+            .appointments
+            .values()
+            .filter(|appointment| appointment.status.occupies_time())
+            .filter(|appointment| from <= appointment.date_time && appointment.date_time < to)
+        {
+            let duration = appointment.duration();
 
-// Define the doctor's calendar
-pub struct DoctorsCalendar {
-    pub appointments: BTreeSet<DoctorsAppointment>,
-}
+            // Find the first time slot from the current time where the
+            // appointment fits in a working hours window and isn't blocked
+            let start = day_time_slots
+                .iter()
+                .copied()
+                .filter(|time_slot| *time_slot >= current)
+                .find(|time_slot| {
+                    self.schedule()
+                        .end_time_respecting_breaks(*time_slot, duration)
+                        .is_ok()
+                        && !DoctorsAppointment {
+                            date_time: *time_slot,
+                            ..appointment.clone()
+                        }
+                        .to_reserved_time_slots()
+                        .iter()
+                        .any(|reserved_time_slot| self.is_blocked(*reserved_time_slot))
+                });
+
+            match start {
+                Some(start) => {
+                    plan.push((appointment.clone(), start));
+                    current = start + duration;
+                },
+                None => plan.push((appointment.clone(), appointment.date_time)),
+            }
+        }
 
-impl Default for DoctorsCalendar {
-    fn default() -> Self {
-        Self::new()
+        plan
     }
-}
 
-impl DoctorsCalendar {
-    // Create a new doctor's calendar
-    pub fn new() -> Self {
-        Self {
-            appointments: BTreeSet::new(),
-        }
+    /// Get the availability of a day, with one entry per working 15 minute time
+    /// slot
+    ///
+    /// An entry is `true` when the time slot is free. The vector always has one
+    /// entry per working time slot of a day in the schedule (32 by default), so
+    /// non-working days return all entries as `false`
+    pub fn day_bitmap(&self, date: NaiveDate) -> Vec<bool> {
+        let from = date.and_time(NaiveTime::MIN);
+        let to = from + Duration::days(1);
+
+        // Get list of available time slots for the whole day
+        let available_time_slots = self.available_single_time_slots(from, to);
+
+        // Check every working time slot of the day against the available ones
+        self.schedule()
+            .day_time_slots(date)
+            .into_iter()
+            .map(|time_slot| available_time_slots.contains(&time_slot))
+            .collect()
     }
 
-    /// Add an appointment to the calendar
-    pub fn add_appointment(&mut self, appointment: DoctorsAppointment) -> Result<(), String> {
-        if appointment
-            .to_reserved_time_slots()
+    /// Count how often every time of the day is free within the given time
+    /// period, per weekday
+    ///
+    /// There is one row per working day of the schedule, in the order of
+    /// `WorkingSchedule::days`, and one column per 15 minute time slot of the
+    /// regular working hours (32 by default). Free time slots of one-off
+    /// windows outside the regular working hours are not counted
+    pub fn availability_heatmap(&self, from: NaiveDateTime, to: NaiveDateTime) -> Vec<Vec<u32>> {
+        let days = self.schedule().days();
+
+        // The columns are the same for every day, the time slots of the regular
+        // working hours windows, whatever one-off windows `from` falls on
+        let columns: BTreeMap<NaiveTime, usize> = self
+            .schedule()
+            .windows()
             .iter()
-            .any(|time_slot| !time_slot.is_working_day_and_hour())
-        {
-            return Err("Appointment is not within working hours".to_string());
-        }
+            .flat_map(|(start, end)| {
+                let date = NaiveDate::default();
 
-        // Get the list of existing appointments within the given time period
-        let existing_appointments = self.booked_appointments(
-            Some(appointment.date_time - appointment.appointment_type.duration()),
-            Some(appointment.date_time),
-        );
+                std::iter::successors(Some(date.and_time(*start)), |time_slot| {
+                    Some(*time_slot + Duration::minutes(15))
+                })
+                .take_while(move |time_slot| *time_slot < date.and_time(*end))
+            })
+            .enumerate()
+            .map(|(column, time_slot)| (time_slot.time(), column))
+            .collect();
 
-        // Check if the appointment overlaps with an existing appointment
-        if existing_appointments.iter().any(|existing_appointment| {
-            existing_appointment.date_time + existing_appointment.appointment_type.duration()
-                > appointment.date_time
-                && existing_appointment.date_time < appointment.date_time
-        }) {
-            return Err("Appointment overlaps with an existing appointment".to_string());
+        let mut heatmap = vec![vec![0; columns.len()]; days.len()];
+
+        for time_slot in self.available_single_time_slots(from, to) {
+            let row = days.iter().position(|day| *day == time_slot.weekday());
+
+            if let (Some(row), Some(column)) = (row, columns.get(&time_slot.time())) {
+                heatmap[row][*column] += 1;
+            }
         }
 
-        // Add the appointment to the calendar
-        self.appointments.insert(appointment);
+        heatmap
+    }
 
-        Ok(())
+    /// Get the list of free time slots for the given time period and
+    /// appointment type
+    pub fn free_slots(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> Vec<NaiveDateTime> {
+        self.free_slots_iter(from, to, appointment_type).collect()
     }
 
-    /// Get the list of booked appointments
-    pub fn booked_appointments(
+    /// Same as `free_slots`, but excludes the slots ending less than
+    /// `pre_break_margin` before the break or the end of the day, as
+    /// appointments often run over
+    pub fn free_slots_with_margin(
         &self,
         from: Option<NaiveDateTime>,
         to: Option<NaiveDateTime>,
-    ) -> Vec<DoctorsAppointment> {
-        // Get the list of booked appointments
-        let booked_appointments = self
-            .appointments
-            .iter()
-            .filter(|appointment| {
-                if let Some(from) = from {
-                    if appointment.date_time < from {
-                        return false;
-                    }
-                }
+        appointment_type: AppointmentType,
+        pre_break_margin: Duration,
+    ) -> Vec<NaiveDateTime> {
+        let duration = self.catalog().duration(appointment_type);
 
-                if let Some(to) = to {
-                    if appointment.date_time > to {
-                        return false;
-                    }
-                }
+        self.free_slots_iter(from, to, appointment_type)
+            .filter(|slot| {
+                let end = *slot + duration + pre_break_margin;
 
-                true
+                // The margin must fit in the working hours window of the slot
+                self.schedule()
+                    .windows_on(slot.date())
+                    .iter()
+                    .any(|(start, window_end)| {
+                        slot.time() >= *start && end <= slot.date().and_time(*window_end)
+                    })
             })
-            .cloned()
-            .collect::<Vec<DoctorsAppointment>>();
+            .collect()
+    }
 
-        booked_appointments
+    /// Same as `free_slots`, but returns the start and end of each free slot
+    pub fn free_slots_spans(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        self.free_slots_iter(from, to, appointment_type)
+            .map(move |slot| (slot, slot + self.catalog().duration(appointment_type)))
+            .collect()
     }
 
-    /// Fill the calendar with random appointments of the given type.
+    /// Get the list of free time slots for the given time period, with enough
+    /// contiguous free working time for the given duration, whatever the
+    /// appointment type
     ///
-    /// The appointments will try to be filled up to the given percentage. If
-    /// the percentage can't be reached because there are no more free slots for
-    /// that appointment type, the function will stop.
-    /// Existing appointments will be counted towards the percentage.
-    pub fn fill_random(
-        &mut self,
-        from: NaiveDateTime,
-        to: NaiveDateTime,
-        appointment_type: AppointmentType,
-        filled_percentage: u8,
-    ) {
-        // Count the total time spots within the given time period
-        let mut total_time_spots: u8 = 0;
+    /// The free working time must not cross a break. Unlike `free_slots`, the
+    /// booking rules of the appointment types don't apply
+    pub fn free_slots_for_duration(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        duration: Duration,
+    ) -> Vec<NaiveDateTime> {
+        let (from, to) = self.resolve_range_for_duration(from, to, duration);
 
-        {
-            let mut current = from;
+        walk_free_slots(self.schedule(), from, to, duration, |time_slot| {
+            self.is_time_slot_available(time_slot)
+        })
+        .collect()
+    }
 
-            loop {
-                current = current.get_next_working_datetime(None);
+    /// Lazily iterate through the free time slots for the given time period
+    /// and appointment type
+    ///
+    /// Time slots are only checked as the iterator advances, so taking the
+    /// first few free time slots doesn't walk the whole time period
+    pub fn free_slots_iter(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        let (from, to) = self.resolve_range(from, to, appointment_type);
+        let duration = self.catalog().duration(appointment_type);
+
+        walk_free_slots(self.schedule(), from, to, duration, move |time_slot| {
+            self.is_time_slot_available(time_slot)
+        })
+        .filter(move |time_slot| self.respects_min_lead(*time_slot, appointment_type))
+        .filter(move |time_slot| {
+            self.within_allowed_windows(*time_slot, appointment_type, duration)
+        })
+    }
 
-                if current > to {
-                    break;
-                }
+    /// Get a page of the free time slots for the given time period and
+    /// appointment type
+    ///
+    /// Skips the first `offset` free time slots, and returns up to `limit` of
+    /// the following ones
+    pub fn free_slots_page(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+        offset: usize,
+        limit: usize,
+    ) -> Vec<NaiveDateTime> {
+        self.free_slots_iter(from, to, appointment_type)
+            .skip(offset)
+            .take(limit)
+            .collect()
+    }
 
-                total_time_spots += 1;
+    /// Render the free time slots of every appointment type for the given
+    /// time period as a Markdown report
+    ///
+    /// Every day with free time slots gets a heading and a table with one row
+    /// per appointment type, listing the start times
+    pub fn free_slots_report_md(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+    ) -> String {
+        // Group the free time slots by day, then by appointment type
+        let mut days: BTreeMap<NaiveDate, BTreeMap<AppointmentType, Vec<NaiveDateTime>>> =
+            BTreeMap::new();
+
+        for appointment_type in AppointmentType::all_by_duration_desc() {
+            for slot in self.free_slots_iter(from, to, appointment_type) {
+                days.entry(slot.date())
+                    .or_default()
+                    .entry(appointment_type)
+                    .or_default()
+                    .push(slot);
             }
         }
 
-        loop {
-            // Get list of free slots for the given time period and appointment type
-            let free_slots = self.free_slots(Some(from), Some(to), appointment_type);
-
-            // If there are no free slots, break the loop
-            if free_slots.is_empty() {
-                break;
+        let mut report = String::new();
+
+        for (date, slots_by_type) in days {
+            report.push_str(&format!("## {}\n\n", date.format("%A %Y-%m-%d")));
+            report.push_str("| Type | Free time slots |\n");
+            report.push_str("| --- | --- |\n");
+
+            for appointment_type in AppointmentType::all_by_duration_desc() {
+                let start_times =
+                    slots_by_type
+                        .get(&appointment_type)
+                        .map_or("-".to_string(), |slots| {
+                            slots
+                                .iter()
+                                .map(|slot| slot.format("%H:%M").to_string())
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        });
+
+                report.push_str(&format!(
+                    "| {} | {} |\n",
+                    appointment_type.display_name(),
+                    start_times
+                ));
             }
 
-            // Get random free slot from list to fill
-            let random_index = rand::thread_rng().gen_range(0..free_slots.len());
-            let random_slot = free_slots[random_index];
+            report.push('\n');
+        }
 
-            // Create a new appointment for the random slot
-            let appointment = DoctorsAppointment::new(random_slot, appointment_type);
+        report
+    }
 
-            // Add the appointment to the calendar
-            self.add_appointment(appointment).unwrap();
+    /// Render the agenda of a day as text, one line per working 15 minute time
+    /// slot
+    ///
+    /// Each line shows the appointment starting in the time slot, `...` while
+    /// it continues, or whether the time slot is free, blocked or held. Breaks
+    /// between the working hours windows get a line of their own. A
+    /// non-working day renders as an empty string
+    pub fn render_day(&self, date: NaiveDate) -> String {
+        let from = date.and_time(NaiveTime::MIN);
+        let to = from + Duration::days(1);
+
+        let mut agenda = String::new();
+        let mut previous: Option<NaiveDateTime> = None;
+
+        for time_slot in self.schedule().working_slots(from, to) {
+            // Mark the break since the previous time slot
+            if let Some(previous) = previous {
+                if !are_connected(previous, time_slot) {
+                    let break_start = previous + Duration::minutes(15);
+                    agenda.push_str(&format!("{}  — break —\n", break_start.format("%H:%M")));
+                }
+            }
 
-            // Get list of booked appointments
-            let booked_appointments = self.booked_appointments(Some(from), Some(to));
+            let appointment = self
+                .occupying_appointments(time_slot, time_slot + Duration::minutes(15))
+                .next();
+
+            let line = match appointment {
+                Some(appointment) if appointment.date_time >= time_slot => {
+                    match &appointment.patient {
+                        Some(patient) => format!(
+                            "{}, {}",
+                            appointment.appointment_type.display_name(),
+                            patient.name
+                        ),
+                        None => appointment.appointment_type.display_name().to_string(),
+                    }
+                },
+                Some(_) => "...".to_string(),
+                None if self.is_blocked(time_slot) => "— blocked —".to_string(),
+                None if self.is_held(time_slot) => "— held —".to_string(),
+                None => "— free —".to_string(),
+            };
+
+            agenda.push_str(&format!("{}  {}\n", time_slot.format("%H:%M"), line));
+            previous = Some(time_slot);
+        }
 
-            // Convert the booked appointments to reserved time slots
-            let reserved_time_slots = booked_appointments
-                .iter()
-                .flat_map(|appointment| appointment.to_reserved_time_slots())
-                .collect::<Vec<NaiveDateTime>>();
+        agenda
+    }
 
-            // Check if the calendar is filled as much as possible up to the given
-            // percentage
-            if reserved_time_slots.len() as f64 / total_time_spots as f64 * 100.0
-                == filled_percentage as f64
-                || (reserved_time_slots.len() + appointment_type.duration_in_time_slots() as usize)
-                    as f64
-                    / total_time_spots as f64
-                    * 100.0
-                    > filled_percentage as f64
-            {
-                break;
-            }
-        }
+    /// Get the first free time slot for the appointment type, starting from
+    /// the given date and time
+    ///
+    /// Looks ahead up to 4 weeks. Returns `None` if there is no free time slot
+    /// within that time
+    pub fn next_available(
+        &self,
+        after: NaiveDateTime,
+        appointment_type: AppointmentType,
+    ) -> Option<NaiveDateTime> {
+        self.next_available_slots(after, appointment_type, 1)
+            .first()
+            .copied()
     }
 
-    /// Get list of available 15 minute time slots for the given time period
-    pub fn available_single_time_slots(
+    /// Get the first free time slot of every appointment type, starting from
+    /// the given date and time, e.g. for a soonest availability overview
+    ///
+    /// Same as `next_available` for each appointment type
+    pub fn next_available_all_types(
         &self,
-        from: NaiveDateTime,
-        to: NaiveDateTime,
+        after: NaiveDateTime,
+    ) -> BTreeMap<AppointmentType, Option<NaiveDateTime>> {
+        AppointmentType::all_by_duration_desc()
+            .map(|appointment_type| {
+                (
+                    appointment_type,
+                    self.next_available(after, appointment_type),
+                )
+            })
+            .collect()
+    }
+
+    /// Get up to `count` free time slots for the appointment type, starting
+    /// from the given date and time
+    ///
+    /// Useful to suggest alternatives when booking a time slot fails. Looks
+    /// ahead up to 4 weeks
+    pub fn next_available_slots(
+        &self,
+        after: NaiveDateTime,
+        appointment_type: AppointmentType,
+        count: usize,
     ) -> Vec<NaiveDateTime> {
-        // Get list of existing appointments within the given time period
-        let existing_appointments = self.booked_appointments(Some(from), Some(to));
+        self.free_slots_iter(
+            Some(after),
+            Some(after + Duration::weeks(4)),
+            appointment_type,
+        )
+        .take(count)
+        .collect()
+    }
 
-        // Convert the booked appointments to reserved time slots
-        let reserved_time_slots: Vec<NaiveDateTime> = existing_appointments
-            .iter()
-            .flat_map(|appointment| appointment.to_reserved_time_slots())
-            .collect();
+    /// Get the nearest free time slot for a follow-up of an appointment, e.g.
+    /// the same time next month
+    ///
+    /// Targets `previous + interval`, snapped to a working time slot, and
+    /// returns the free time slot for the appointment type closest to it,
+    /// before or after. On a tie the later one wins, so the interval isn't
+    /// shortened. Looks up to 4 weeks around the target, but never at or
+    /// before `previous`
+    pub fn next_recurrence_slot(
+        &self,
+        previous: NaiveDateTime,
+        appointment_type: AppointmentType,
+        interval: Duration,
+    ) -> Option<NaiveDateTime> {
+        let target = self.schedule().snap_to_working_slot(previous + interval)?;
+        let next = self.next_available(target, appointment_type);
+
+        // Only look back as far as strictly closer than the next free time slot
+        let lookback = next.map_or(Duration::weeks(4), |next| {
+            next - target - Duration::minutes(15)
+        });
+        let earliest = (target - lookback).max(previous + Duration::minutes(15));
+
+        let before = if earliest < target {
+            self.free_slots_iter(
+                Some(earliest),
+                Some(target - Duration::minutes(15)),
+                appointment_type,
+            )
+            .last()
+        } else {
+            None
+        };
 
-        // Create the list of available time slots
-        let mut available_time_slots = vec![];
+        before.or(next)
+    }
 
-        // Start a loop from the `from` time to the `to` time
-        let mut current = from;
+    /// Check if the 15 minute time slot is free to book
+    ///
+    /// The slot must be on a 15 minute mark within the working hours, and
+    /// neither reserved by an appointment nor blocked or held. An appointment
+    /// starting there may still not fit, see `free_slots` for that
+    pub fn is_slot_free(&self, slot: NaiveDateTime) -> bool {
+        slot.minute().is_multiple_of(15)
+            && slot.second() == 0
+            && slot.nanosecond() == 0
+            && self.is_time_slot_available(slot)
+    }
 
-        while current < to {
-            // Check if the current time is within the working hours
-            if current.is_working_day_and_hour() {
-                // Check if the current time has already been reserved
-                if !reserved_time_slots.contains(&current) {
-                    // Add the current time to the list of available time slots
-                    available_time_slots.push(current);
-                }
-            }
+    /// Check if the 15 minute time slot is within the working hours, and
+    /// neither reserved by an appointment nor blocked or held
+    fn is_time_slot_available(&self, time_slot: NaiveDateTime) -> bool {
+        self.schedule().is_working_day_and_hour(time_slot)
+            && !self.is_blocked(time_slot)
+            && !self.is_held(time_slot)
+            && !self.is_reserved(time_slot)
+    }
 
-            // Increment the current time by 15 minutes
-            current = current.get_next_working_datetime(None);
-        }
+    /// Check if the 15 minute time slot is reserved by an appointment
+    fn is_reserved(&self, time_slot: NaiveDateTime) -> bool {
+        self.occupying_appointments(time_slot, time_slot + Duration::minutes(15))
+            .next()
+            .is_some()
+    }
 
-        available_time_slots
+    /// Get the appointments occupying their time slots that overlap with the
+    /// time from `start` until `end`, in chronological order
+    ///
+    /// The appointments are looked up in the interval index, so long
+    /// appointments starting well before `start` are found without scanning
+    fn occupying_appointments(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = &DoctorsAppointment> {
+        self.occupancy.overlapping(start, end)
     }
 
-    /// Get the list of free time slots for the given time period and
-    /// appointment type
-    pub fn free_slots(
+    /// Get the appointments starting from `start` up to, but excluding, `end`
+    fn appointments_starting_in(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = &DoctorsAppointment> {
+        // Appointments are keyed on their date and time first, and these are
+        // the smallest keys starting at the given times
+        self.appointments
+            .range((start, None)..(end, None))
+            .map(|(_, appointment)| appointment)
+    }
+
+    /// Return the free slots, filtered to one appointment per 60 minute window
+    /// (see `set_optimizer_window`). The priority goes to the long-duration
+    /// appointments
+    ///
+    /// The slots are sorted in ascending order, and the output is the same for
+    /// the same calendar: among equally good slots of a window, the earliest
+    /// one is chosen
+    pub fn free_slots_optimized(
         &self,
         from: Option<NaiveDateTime>,
         to: Option<NaiveDateTime>,
         appointment_type: AppointmentType,
     ) -> Vec<NaiveDateTime> {
-        // In case `from` is not provided, set it to the current time
-        let from = if let Some(from) = from {
-            from
-        } else {
-            now_next_15_mark()
-        };
-
-        // In case `to` is not provided, set it to the end of the day this Friday
-        let to = if let Some(to) = to {
-            to.calculate_end_time(appointment_type)
-        } else {
-            end_of_week()
-        };
-
-        // Get list of available time slots
-        let available_time_slots = self.available_single_time_slots(from, to);
+        self.free_slots_optimized_explained(from, to, appointment_type)
+            .into_iter()
+            .map(|optimized_slot| optimized_slot.slot)
+            .collect()
+    }
 
-        // Filter the available time slots by the appointment type
-        let filtered_time_slots = match appointment_type {
-            // For urgent appointments, no need to filter the time slots
-            AppointmentType::UrgentDentalAppointment => available_time_slots,
-            AppointmentType::DentalCheckUp | AppointmentType::ImplantConsultation => {
-                available_time_slots
-                    .iter()
-                    .filter(|time_slot| {
-                        // Check if the following time slots are available for
-                        // the appointment type to fit
-                        let mut current = **time_slot;
-                        let mut available = true;
-
-                        for _ in 0..appointment_type.duration().num_minutes() / 15 {
-                            if !available_time_slots.contains(&current) {
-                                available = false;
-                                break;
-                            }
-
-                            current += Duration::minutes(15);
-                        }
+    /// Same as `free_slots_optimized`, but the candidate slots are compared
+    /// by their capacity for the appointment types in the given priority order,
+    /// e.g. to prioritize urgent appointments
+    ///
+    /// Appointment types missing from the priority list are not compared
+    pub fn free_slots_optimized_with_priority(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+        priority: &[AppointmentType],
+    ) -> Vec<NaiveDateTime> {
+        self.optimize_free_slots(from, to, appointment_type, priority)
+            .into_iter()
+            .map(|optimized_slot| optimized_slot.slot)
+            .collect()
+    }
 
-                        available
-                    })
-                    .copied()
-                    .collect()
-            },
-        };
+    /// Same as `free_slots_optimized`, but also returns, per window,
+    /// the capacity per appointment type computed for the chosen slot
+    pub fn free_slots_optimized_explained(
+        &self,
+        from: Option<NaiveDateTime>,
+        to: Option<NaiveDateTime>,
+        appointment_type: AppointmentType,
+    ) -> Vec<OptimizedSlot> {
+        // The priority goes to the long-duration appointments
+        let priority: Vec<AppointmentType> = AppointmentType::all_by_duration_desc().collect();
 
-        filtered_time_slots
+        self.optimize_free_slots(from, to, appointment_type, &priority)
     }
 
-    /// Return the free slots, filtered to one appointment per 60 minute window.
-    /// The priority goes to the long-duration appointments
-    pub fn free_slots_optimized(
+    /// Choose one free slot per window, comparing the candidate
+    /// slots by their capacity for the appointment types in priority order
+    fn optimize_free_slots(
         &self,
         from: Option<NaiveDateTime>,
         to: Option<NaiveDateTime>,
         appointment_type: AppointmentType,
-    ) -> Vec<NaiveDateTime> {
+        priority: &[AppointmentType],
+    ) -> Vec<OptimizedSlot> {
         // Get the list of free slots
         let free_slots = self.free_slots(from, to, appointment_type);
 
-        // In case `from` is not provided, set it to the current time
-        let from = if let Some(from) = from {
-            from
-        } else {
-            now_next_15_mark()
-        };
-
-        // In case `to` is not provided, set it to the end of the day this Friday
-        let to = if let Some(to) = to {
-            to.calculate_end_time(appointment_type)
-        } else {
-            end_of_week()
-        };
+        let (from, to) = self.resolve_range(from, to, appointment_type);
 
         let available_time_slots = self.available_single_time_slots(from, to);
 
-        // Split into groups of 60 minutes windows
+        let window_minutes = self.optimizer_window.num_minutes();
+
+        // Split into groups of windows
         let mut time_windows: BTreeMap<NaiveDateTime, Vec<&NaiveDateTime>> = BTreeMap::new();
-        // Loop through the free slots and group them into 60 minute windows
+        // Loop through the free slots and group them into windows
         for slot in free_slots.iter() {
-            // Get the start of the 60 minute window
-            let window_start = slot.with_minute(0).unwrap().with_second(0).unwrap();
+            let window_start = window_start(*slot, window_minutes);
 
             // Insert window_start key in windows BTreeMap if it doesn't exist.
             // And add the slot as part of the array value
             time_windows.entry(window_start).or_default().push(slot);
         }
 
-        // The list of optimized free slots, one per 60 minute window
+        // The list of optimized free slots, one per window
         let mut optimized_free_slots = vec![];
 
         // Loop through the windows and create a new list of connected free slots to
         // each value in the window
-        for (_, window_time_slots) in time_windows.iter() {
+        for (window_start, window_time_slots) in time_windows.iter() {
             let mut ideal_slot: Option<&NaiveDateTime> = None;
             let mut ideal_count_appointment_space_per_type: BTreeMap<AppointmentType, u8> =
                 BTreeMap::new();
@@ -532,15 +3458,17 @@ impl DoctorsCalendar {
                 let mut count_connected_single_slots_forwards = 0;
                 let mut count_connected_single_slots_backwards = 0;
 
-                // Find index of the current time slot in the available_time_slots
-                let index = available_time_slots
-                    .iter()
-                    .position(|x| x == *time_slot)
-                    .unwrap();
+                // Find index of the current time slot in the available_time_slots. Skip
+                // the time slot if it's not there, as there are no connected slots to
+                // count
+                let Some(index) = available_time_slots.iter().position(|x| x == *time_slot) else {
+                    continue;
+                };
 
                 // Move the index forward to check for connected slots excluding the current
                 // appointment
-                let mut index_forward = index + appointment_type.duration_in_time_slots() as usize;
+                let mut index_forward =
+                    index + self.duration_in_time_slots(appointment_type) as usize;
 
                 // Add all the next slots to the connected_slots if they are
                 // available_time_slots
@@ -557,9 +3485,10 @@ impl DoctorsCalendar {
 
                     // If next slot is directly connected increase the count of connected slots,
                     // otherwise break
-                    if available_time_slots[index_forward] - available_time_slots[index_forward - 1]
-                        == Duration::minutes(15)
-                    {
+                    if are_connected(
+                        available_time_slots[index_forward - 1],
+                        available_time_slots[index_forward],
+                    ) {
                         count_connected_single_slots_forwards += 1;
                     } else {
                         break;
@@ -588,10 +3517,10 @@ impl DoctorsCalendar {
 
                     // If previous slot is directly connected increase the count of connected slots,
                     // otherwise break
-                    if available_time_slots[index_backward + 1]
-                        - available_time_slots[index_backward]
-                        == Duration::minutes(15)
-                    {
+                    if are_connected(
+                        available_time_slots[index_backward],
+                        available_time_slots[index_backward + 1],
+                    ) {
                         count_connected_single_slots_backwards += 1;
                     } else {
                         break;
@@ -606,9 +3535,9 @@ impl DoctorsCalendar {
                     // Get the number of appointments that fit in the connected slots for the
                     // current appointment type
                     let num_appointments = count_connected_single_slots_forwards
-                        / appointment_type.duration_in_time_slots()
+                        / self.duration_in_time_slots(appointment_type)
                         + count_connected_single_slots_backwards
-                            / appointment_type.duration_in_time_slots();
+                            / self.duration_in_time_slots(appointment_type);
 
                     // Add the number of appointments to the current count of appointment space per
                     // type
@@ -618,31 +3547,36 @@ impl DoctorsCalendar {
                     // Update connected count of slots, with the remainder of after removing the
                     // spots for the current appointment type
                     count_connected_single_slots_forwards %=
-                        appointment_type.duration_in_time_slots();
+                        self.duration_in_time_slots(appointment_type);
                     count_connected_single_slots_backwards %=
-                        appointment_type.duration_in_time_slots();
+                        self.duration_in_time_slots(appointment_type);
                 }
 
                 if ideal_slot.is_some() {
                     // Check if the current slot contains higher number of big appointment types
                     // than the current ideal slot, and if it does, set the current slot as the
                     // ideal one
-                    let appointment_iter = AppointmentTypeIter::new();
-                    for appointment_type in appointment_iter {
+                    for appointment_type in priority {
+                        // Missing counts are treated as no space for the appointment type
+                        let current_count = current_count_appointment_space_per_type
+                            .get(appointment_type)
+                            .copied()
+                            .unwrap_or(0);
+                        let ideal_count = ideal_count_appointment_space_per_type
+                            .get(appointment_type)
+                            .copied()
+                            .unwrap_or(0);
+
                         // If the current slot contains smaller number of big appointment types than
                         // the ideal slot, break
-                        if current_count_appointment_space_per_type[&appointment_type]
-                            < ideal_count_appointment_space_per_type[&appointment_type]
-                        {
+                        if current_count < ideal_count {
                             break;
                         }
 
                         // If the current slot contains higher number of big appointment types than
                         // the ideal slot, set the current slot as the ideal slot. Otherwise
                         // continue to the next/shorter appointment type
-                        if current_count_appointment_space_per_type[&appointment_type]
-                            > ideal_count_appointment_space_per_type[&appointment_type]
-                        {
+                        if current_count > ideal_count {
                             ideal_slot = Some(time_slot);
                             ideal_count_appointment_space_per_type =
                                 current_count_appointment_space_per_type;
@@ -659,7 +3593,11 @@ impl DoctorsCalendar {
 
             // Add the ideal slot to the optimized free slots
             if let Some(ideal_slot) = ideal_slot {
-                optimized_free_slots.push(*ideal_slot);
+                optimized_free_slots.push(OptimizedSlot {
+                    window_start: *window_start,
+                    slot: *ideal_slot,
+                    capacity: ideal_count_appointment_space_per_type,
+                });
             }
         }
 
@@ -0,0 +1,65 @@
+//! Append-only log of the changes made to the calendar
+
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+
+use crate::appointment::DoctorsAppointment;
+
+/// Change made to the calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+    /// An appointment was added
+    Add,
+    /// An appointment was cancelled
+    Cancel,
+    /// The type of an appointment was changed
+    ChangeType,
+    /// An appointment was moved to another date and time
+    Reschedule,
+    /// The status of an appointment was changed
+    StatusChange,
+}
+
+/// Entry of the audit log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// When the change was made, according to the calendar's clock
+    pub at: NaiveDateTime,
+    pub operation: AuditOperation,
+    /// The appointment after the change, or the cancelled appointment
+    pub appointment: DoctorsAppointment,
+}
+
+/// Append-only log of the changes made to the calendar, in the order they were
+/// made
+#[derive(Debug, Default, Clone)]
+pub struct AuditLog {
+    entries: Arc<Vec<AuditEntry>>,
+}
+
+impl AuditLog {
+    /// Create a new empty audit log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an entry to the log
+    pub fn record(
+        &mut self,
+        at: NaiveDateTime,
+        operation: AuditOperation,
+        appointment: DoctorsAppointment,
+    ) {
+        Arc::make_mut(&mut self.entries).push(AuditEntry {
+            at,
+            operation,
+            appointment,
+        });
+    }
+
+    /// Get the entries of the log, from the oldest to the newest
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+}
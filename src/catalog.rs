@@ -0,0 +1,90 @@
+//! Booking rules per appointment type
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveTime};
+
+use crate::appointment::AppointmentType;
+
+/// Booking rules of the appointment types offered by the practice
+///
+/// By default, every appointment type can be booked at any time, and lasts its
+/// default duration
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AppointmentCatalog {
+    min_leads: BTreeMap<AppointmentType, Duration>,
+    durations: BTreeMap<AppointmentType, Duration>,
+    allowed_windows: BTreeMap<AppointmentType, Vec<(NaiveTime, NaiveTime)>>,
+}
+
+impl AppointmentCatalog {
+    /// Create a new catalog without booking rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require the appointment type to be booked at least `min_lead` before
+    /// it starts
+    pub fn with_min_lead(mut self, appointment_type: AppointmentType, min_lead: Duration) -> Self {
+        self.min_leads.insert(appointment_type, min_lead);
+        self
+    }
+
+    /// Change the duration of the appointment type, which must be a positive
+    /// multiple of 15 minutes to be booked
+    ///
+    /// Only new appointments get the duration: booked appointments keep the
+    /// duration they were booked with
+    pub fn with_duration(mut self, appointment_type: AppointmentType, duration: Duration) -> Self {
+        self.durations.insert(appointment_type, duration);
+        self
+    }
+
+    /// Get the duration of the appointment type, its default one unless changed
+    pub fn duration(&self, appointment_type: AppointmentType) -> Duration {
+        self.durations
+            .get(&appointment_type)
+            .copied()
+            .unwrap_or(appointment_type.duration())
+    }
+
+    /// Get the minimum notice required to book the appointment type, if any
+    pub fn min_lead(&self, appointment_type: AppointmentType) -> Option<Duration> {
+        self.min_leads.get(&appointment_type).copied()
+    }
+
+    /// Only offer the appointment type from `start` to `end`, e.g. implant
+    /// consultations in the mornings
+    ///
+    /// Can be called several times to allow several windows. Appointment types
+    /// without allowed windows are offered during all the working hours
+    pub fn with_allowed_window(
+        mut self,
+        appointment_type: AppointmentType,
+        start: NaiveTime,
+        end: NaiveTime,
+    ) -> Self {
+        self.allowed_windows
+            .entry(appointment_type)
+            .or_default()
+            .push((start, end));
+        self
+    }
+
+    /// Check if an appointment of the type from `start` to `end` is within one
+    /// of its allowed windows, if any
+    pub fn is_allowed(
+        &self,
+        appointment_type: AppointmentType,
+        start: NaiveTime,
+        end: NaiveTime,
+    ) -> bool {
+        self.allowed_windows
+            .get(&appointment_type)
+            .is_none_or(|windows| {
+                windows
+                    .iter()
+                    .any(|(window_start, window_end)| start >= *window_start && end <= *window_end)
+            })
+    }
+}
@@ -1,28 +1,159 @@
 //! This module contains the command-line interface (CLI) functions for the
 //! application.
 
-use chrono::{NaiveDateTime, ParseError, Timelike};
+use std::path::PathBuf;
+
+use chrono::{NaiveDateTime, NaiveTime, ParseError, Timelike, Weekday};
+use clap::{Parser, Subcommand};
 use dialoguer::{theme::ColorfulTheme, Input, Select};
+use serde::Serialize;
+
+use crate::{
+    appointment::{AppointmentType, DoctorsAppointment},
+    schedule::WorkingSchedule,
+    utils::{format_slot, DEFAULT_SLOT_FORMAT},
+};
+
+/// Command-line arguments. Without a subcommand, the interactive menus are used
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// JSON Lines file to load the appointments from and save them to
+    #[arg(long, global = true)]
+    pub calendar: Option<PathBuf>,
+
+    /// Print the results as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Non-interactive commands
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Add a new appointment
+    Add {
+        /// Appointment type, e.g. "checkup", "implant" or "urgent"
+        #[arg(long = "type")]
+        appointment_type: AppointmentType,
+
+        /// Start of the appointment (YYYY-MM-DD HH:MM)
+        #[arg(long, value_parser = parse_date)]
+        at: NaiveDateTime,
+    },
+    /// List booked appointments
+    Booked {
+        /// Start of the range (YYYY-MM-DD HH:MM)
+        #[arg(long, value_parser = parse_date)]
+        from: Option<NaiveDateTime>,
+
+        /// End of the range (YYYY-MM-DD HH:MM)
+        #[arg(long, value_parser = parse_date)]
+        to: Option<NaiveDateTime>,
+    },
+    /// List free time slots for an appointment type
+    FreeSlots {
+        /// Appointment type, e.g. "checkup", "implant" or "urgent"
+        #[arg(long = "type")]
+        appointment_type: AppointmentType,
 
-use crate::appointment::AppointmentType;
+        /// Start of the range (YYYY-MM-DD HH:MM) [default: now]
+        #[arg(long, value_parser = parse_date)]
+        from: Option<NaiveDateTime>,
+
+        /// End of the range (YYYY-MM-DD HH:MM) [default: end of the week]
+        #[arg(long, value_parser = parse_date)]
+        to: Option<NaiveDateTime>,
+
+        /// Show at most one time slot per 60 minutes, prioritizing
+        /// long-duration appointments
+        #[arg(long)]
+        optimized: bool,
+    },
+}
+
+impl Args {
+    /// Get the output format selected by the arguments
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Human
+        }
+    }
+}
+
+/// Format used to print results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One result per line, for reading in the terminal
+    #[default]
+    Human,
+    /// A single JSON value, with ISO 8601 timestamps
+    Json,
+}
+
+/// Print a list of time slots in the given output format
+pub fn print_slots(slots: &[NaiveDateTime], output_format: OutputFormat) {
+    match output_format {
+        OutputFormat::Human => slots
+            .iter()
+            .for_each(|slot| println!("{}", format_slot(*slot, DEFAULT_SLOT_FORMAT))),
+        OutputFormat::Json => println!("{}", serde_json::to_string(slots).unwrap()),
+    }
+}
+
+/// Appointment as printed in the JSON output, along with the color of its
+/// type
+#[derive(Debug, Serialize)]
+struct AppointmentOutput<'a> {
+    #[serde(flatten)]
+    appointment: &'a DoctorsAppointment,
+    color: &'static str,
+}
+
+/// Print a list of appointments in the given output format
+pub fn print_appointments(appointments: &[DoctorsAppointment], output_format: OutputFormat) {
+    match output_format {
+        OutputFormat::Human => appointments.iter().for_each(|appointment| {
+            println!(
+                "Date: {}, Type: {}",
+                format_slot(appointment.date_time, DEFAULT_SLOT_FORMAT),
+                appointment.appointment_type.display_name()
+            );
+        }),
+        OutputFormat::Json => {
+            let appointments: Vec<AppointmentOutput> = appointments
+                .iter()
+                .map(|appointment| AppointmentOutput {
+                    appointment,
+                    color: appointment.appointment_type.color(),
+                })
+                .collect();
+
+            println!("{}", serde_json::to_string(&appointments).unwrap())
+        },
+    }
+}
+
+/// Parse a date from a command-line argument
+pub fn parse_date(date_str: &str) -> Result<NaiveDateTime, ParseError> {
+    NaiveDateTime::parse_from_str(date_str, DEFAULT_SLOT_FORMAT)
+}
 
 
 /// Get appointment type from the user
 pub fn get_appointment_type_from_user() -> AppointmentType {
-    let actions_appointment_type = [
-        (
-            AppointmentType::UrgentDentalAppointment.display_name(),
-            AppointmentType::UrgentDentalAppointment,
-        ),
-        (
-            AppointmentType::DentalCheckUp.display_name(),
-            AppointmentType::DentalCheckUp,
-        ),
-        (
-            AppointmentType::ImplantConsultation.display_name(),
-            AppointmentType::ImplantConsultation,
-        ),
-    ];
+    // List the appointment types from the shortest to the longest duration
+    let actions_appointment_type: Vec<(&str, AppointmentType)> =
+        AppointmentType::all_by_duration_desc()
+            .collect::<Vec<AppointmentType>>()
+            .into_iter()
+            .rev()
+            .map(|appointment_type| (appointment_type.display_name(), appointment_type))
+            .collect();
 
     // Extract the string descriptions to display in the menu
     let action_descriptions_appointment_type: Vec<&str> = actions_appointment_type
@@ -48,7 +179,7 @@ pub fn get_date_from_user(prompt: &str) -> Result<NaiveDateTime, ParseError> {
         .interact_text()
         .unwrap();
 
-    NaiveDateTime::parse_from_str(&date_str, "%Y-%m-%d %H:%M")
+    parse_date(&date_str)
 }
 
 /// Enum that defines the actions that can be performed through the command-line
@@ -61,6 +192,8 @@ pub enum Action {
     AddNewAppointment,
     ListFreeTimeSlots,
     ListOptimizedFreeTimeSlots,
+    DayAgenda,
+    EditSchedule,
     Quit,
 }
 
@@ -76,7 +209,9 @@ pub fn main_menu() -> Action {
             "List optimized free time slots",
             Action::ListOptimizedFreeTimeSlots,
         ),
+        ("Day agenda of the `From` date", Action::DayAgenda),
         ("Fill random", Action::FillRandom),
+        ("Edit working schedule", Action::EditSchedule),
         ("Set `From` date", Action::SetFromDate),
         ("Set `To` date", Action::SetToDate),
         ("Quit", Action::Quit),
@@ -174,3 +309,97 @@ pub fn add_new_appointment_menu() -> (AppointmentType, Option<NaiveDateTime>) {
 
     (appointment_type, date)
 }
+
+/// Format the working hours windows of a schedule, e.g. "08:00-12:00,
+/// 13:00-17:00"
+pub fn format_windows(schedule: &WorkingSchedule) -> String {
+    schedule
+        .windows()
+        .iter()
+        .map(|(start, end)| format!("{}-{}", start.format("%H:%M"), end.format("%H:%M")))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Format the working days of a schedule, e.g. "Mon, Tue, Wed"
+pub fn format_days(schedule: &WorkingSchedule) -> String {
+    schedule
+        .days()
+        .iter()
+        .map(|day| day.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Parse working hours windows separated by commas, e.g. "08:00-12:00,
+/// 13:00-17:00"
+pub fn parse_windows(windows_str: &str) -> Result<Vec<(NaiveTime, NaiveTime)>, String> {
+    windows_str
+        .split(',')
+        .map(|window| {
+            let (start, end) = window
+                .trim()
+                .split_once('-')
+                .ok_or_else(|| format!("Invalid working hours window: {}", window.trim()))?;
+
+            let parse_time = |time: &str| {
+                NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                    .map_err(|e| format!("Invalid time {}: {}", time.trim(), e))
+            };
+
+            Ok((parse_time(start)?, parse_time(end)?))
+        })
+        .collect()
+}
+
+/// Parse weekdays separated by commas, e.g. "Sun, Mon, Tue"
+pub fn parse_days(days_str: &str) -> Result<Vec<Weekday>, String> {
+    days_str
+        .split(',')
+        .map(|day| {
+            day.trim()
+                .parse::<Weekday>()
+                .map_err(|_| format!("Invalid weekday: {}", day.trim()))
+        })
+        .collect()
+}
+
+/// Display the EditSchedule menu and return the new schedule, or `None` if
+/// the input is invalid
+pub fn edit_schedule_menu(schedule: &WorkingSchedule) -> Option<WorkingSchedule> {
+    println!("Working hours: {}", format_windows(schedule));
+    println!("Working days: {}", format_days(schedule));
+
+    let windows: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter working hours (HH:MM-HH:MM, ...)")
+        .default(format_windows(schedule))
+        .interact_text()
+        .unwrap();
+
+    let days: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Enter working days (Mon, Tue, ...)")
+        .default(format_days(schedule))
+        .interact_text()
+        .unwrap();
+
+    let schedule = parse_windows(&windows).and_then(|windows| {
+        let days = parse_days(&days)?;
+
+        windows
+            .into_iter()
+            .fold(WorkingSchedule::builder(), |builder, (start, end)| {
+                builder.window(start, end)
+            })
+            .working_days(&days)
+            .build()
+            .map_err(|e| e.to_string())
+    });
+
+    match schedule {
+        Ok(schedule) => Some(schedule),
+        Err(e) => {
+            println!("Failed to edit working schedule: {}", e);
+            None
+        },
+    }
+}
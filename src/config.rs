@@ -0,0 +1,218 @@
+//! Configuration shared by the calendars of a clinic
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Deserialize;
+
+use crate::{
+    appointment::{AppointmentType, Block},
+    catalog::AppointmentCatalog,
+    error::ConfigError,
+    schedule::{WorkingSchedule, WorkingScheduleBuilder},
+};
+
+/// Length of a time slot, the only one the calendars support
+const SLOT_MINUTES: i64 = 15;
+
+/// Working schedule, booking rules and closures of a clinic, shared by the
+/// calendars of its doctors through an `Arc`
+///
+/// Closures can be added through a shared reference, so every calendar
+/// sharing the configuration reflects them right away, and its version
+/// changes. Snapshots of the calendars keep the closures they were taken with
+#[derive(Debug, Default, Clone)]
+pub struct ClinicConfig {
+    pub(crate) schedule: WorkingSchedule,
+    pub(crate) catalog: AppointmentCatalog,
+    // Shared by the copies of the configuration, see `DoctorsCalendar::set_schedule`
+    closures: Arc<RwLock<Closures>>,
+}
+
+/// Closures of the clinic, copied on write so snapshots can keep them
+#[derive(Debug, Default)]
+struct Closures {
+    blocks: Arc<BTreeSet<Block>>,
+    // Incremented on every change, see `DoctorsCalendar::version`
+    version: u64,
+}
+
+/// Clinic configuration file, e.g.
+///
+/// ```json
+/// {
+///     "schedule": {
+///         "windows": [["08:00:00", "12:00:00"], ["13:00:00", "17:00:00"]],
+///         "days": ["Mon", "Tue", "Wed", "Thu", "Fri"]
+///     },
+///     "holidays": ["2024-12-25"],
+///     "slot_minutes": 15,
+///     "durations_minutes": { "DentalCheckUp": 45 }
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClinicConfigFile {
+    schedule: WorkingScheduleBuilder,
+    #[serde(default)]
+    holidays: Vec<NaiveDate>,
+    #[serde(default = "default_slot_minutes")]
+    slot_minutes: i64,
+    #[serde(default)]
+    durations_minutes: BTreeMap<AppointmentType, i64>,
+}
+
+fn default_slot_minutes() -> i64 {
+    SLOT_MINUTES
+}
+
+impl ClinicConfig {
+    /// Create a new configuration with the given working schedule and booking
+    /// rules, and without closures
+    pub fn new(schedule: WorkingSchedule, catalog: AppointmentCatalog) -> Self {
+        Self {
+            schedule,
+            catalog,
+            closures: Arc::default(),
+        }
+    }
+
+    /// Load the configuration from a JSON file, with the working hours and
+    /// days, the holidays, the slot size and the appointment type durations
+    ///
+    /// The working schedule is validated like `WorkingScheduleBuilder::build`.
+    /// The holidays close the clinic for the whole day. The slot size is
+    /// optional, and can only be 15 minutes. Appointment types without a
+    /// duration keep their default one
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let file = File::open(path).map_err(ConfigError::Io)?;
+        let config_file: ClinicConfigFile =
+            serde_json::from_reader(BufReader::new(file)).map_err(ConfigError::Parse)?;
+
+        if config_file.slot_minutes != SLOT_MINUTES {
+            return Err(ConfigError::UnsupportedSlotSize(config_file.slot_minutes));
+        }
+
+        let schedule = config_file
+            .schedule
+            .build()
+            .map_err(ConfigError::Schedule)?;
+
+        let mut catalog = AppointmentCatalog::new();
+
+        for (appointment_type, minutes) in config_file.durations_minutes {
+            if minutes <= 0 || minutes % SLOT_MINUTES != 0 {
+                return Err(ConfigError::InvalidDuration(appointment_type));
+            }
+
+            catalog = catalog.with_duration(appointment_type, Duration::minutes(minutes));
+        }
+
+        let config = Self::new(schedule, catalog);
+
+        for holiday in config_file.holidays {
+            config.add_closure(holiday, None);
+        }
+
+        Ok(config)
+    }
+
+    /// Get the working schedule of the clinic
+    pub fn schedule(&self) -> &WorkingSchedule {
+        &self.schedule
+    }
+
+    /// Get the booking rules of the appointment types
+    pub fn catalog(&self) -> &AppointmentCatalog {
+        &self.catalog
+    }
+
+    /// Close the clinic on the given date, for the whole day or only the given
+    /// time window, e.g. a public holiday
+    ///
+    /// The closure is returned so it can be lifted with `remove_closure`
+    pub fn add_closure(&self, date: NaiveDate, window: Option<(NaiveTime, NaiveTime)>) -> Block {
+        let block = Block::closure(date, window);
+
+        let mut closures = self
+            .closures
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if Arc::make_mut(&mut closures.blocks).insert(block.clone()) {
+            closures.version += 1;
+        }
+
+        block
+    }
+
+    /// Remove a closure of the clinic. Returns whether the closure existed
+    pub fn remove_closure(&self, closure: &Block) -> bool {
+        let mut closures = self
+            .closures
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let removed = Arc::make_mut(&mut closures.blocks).remove(closure);
+
+        if removed {
+            closures.version += 1;
+        }
+
+        removed
+    }
+
+    /// Get the closures of the clinic, in chronological order
+    pub fn closures(&self) -> Vec<Block> {
+        self.current_closures().iter().cloned().collect()
+    }
+
+    /// Get the version of the closures, which changes whenever a closure is
+    /// added or removed
+    pub fn closures_version(&self) -> u64 {
+        self.closures
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .version
+    }
+
+    /// Check if the clinic is closed during any part of the 15 minute time
+    /// slot
+    pub fn is_closed(&self, time_slot: NaiveDateTime) -> bool {
+        self.current_closures()
+            .iter()
+            .any(|closure| closure.covers(time_slot))
+    }
+
+    /// Copy the configuration, with the current closures, without sharing
+    /// the later changes of the closures
+    ///
+    /// Cheap, as the closures are only copied when they next change
+    pub(crate) fn frozen(&self) -> Self {
+        let closures = self.closures.read().unwrap_or_else(PoisonError::into_inner);
+
+        Self {
+            schedule: self.schedule.clone(),
+            catalog: self.catalog.clone(),
+            closures: Arc::new(RwLock::new(Closures {
+                blocks: closures.blocks.clone(),
+                version: closures.version,
+            })),
+        }
+    }
+
+    /// Get the current closures, without holding the lock
+    fn current_closures(&self) -> Arc<BTreeSet<Block>> {
+        self.closures
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .blocks
+            .clone()
+    }
+}
@@ -0,0 +1,207 @@
+//! Errors returned by the appointment system
+
+use std::{fmt, io};
+
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+
+use crate::appointment::AppointmentType;
+
+/// Errors returned by the doctor's calendar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CalendarError {
+    /// The appointment is not within working hours
+    OutsideWorkingHours,
+    /// The appointment starts in one working hours window and ends in the next
+    SpansBreak,
+    /// The appointment overlaps with an existing appointment
+    Overlap,
+    /// The appointment overlaps with a blocked time
+    Blocked,
+    /// The appointment overlaps with a time slot held for another booking
+    Held,
+    /// There is no appointment at the given time
+    NotFound,
+    /// There is no free time slot for the appointment in the given time period
+    NoFreeSlot,
+    /// There is no hold with the given id, or it has expired
+    HoldNotFound,
+    /// The appointment starts sooner than the minimum notice of its type
+    InsufficientNotice,
+    /// The appointment is outside the allowed windows of its type
+    OutsideAllowedWindow,
+    /// The appointment duration is not a positive multiple of 15 minutes, or
+    /// can't be overridden for the appointment type
+    InvalidDuration,
+    /// The appointment is longer than every working hours window, so it can
+    /// never be booked
+    TooLongForAnyWindow(Duration),
+    /// The appointment doesn't start on a 15 minute mark
+    UnalignedStart(NaiveDateTime),
+    /// The name doesn't match any appointment type
+    UnknownAppointmentType(String),
+    /// The end of the range is before its start
+    InvertedRange {
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    },
+    /// The calendar changed since the expected version was read
+    StaleVersion { expected: u64, current: u64 },
+    /// Another appointment in the calendar already has the appointment's id
+    DuplicateId,
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalendarError::OutsideWorkingHours => {
+                write!(f, "Appointment is not within working hours")
+            },
+            CalendarError::SpansBreak => write!(f, "Appointment spans the break"),
+            CalendarError::Overlap => {
+                write!(f, "Appointment overlaps with an existing appointment")
+            },
+            CalendarError::Blocked => write!(f, "Appointment overlaps with a blocked time"),
+            CalendarError::Held => write!(f, "Appointment overlaps with a held time slot"),
+            CalendarError::NotFound => write!(f, "No appointment found at the given time"),
+            CalendarError::NoFreeSlot => write!(f, "No free time slot for the appointment"),
+            CalendarError::HoldNotFound => write!(f, "No active hold found with the given id"),
+            CalendarError::InsufficientNotice => {
+                write!(
+                    f,
+                    "Appointment doesn't respect the minimum notice of its type"
+                )
+            },
+            CalendarError::OutsideAllowedWindow => {
+                write!(
+                    f,
+                    "Appointment is outside the time windows its type is offered in"
+                )
+            },
+            CalendarError::InvalidDuration => write!(f, "Invalid appointment duration"),
+            CalendarError::TooLongForAnyWindow(duration) => write!(
+                f,
+                "Appointment of {} minutes doesn't fit in any working hours window",
+                duration.num_minutes()
+            ),
+            CalendarError::UnalignedStart(date_time) => {
+                write!(
+                    f,
+                    "Appointment start {} is not on a 15 minute mark",
+                    date_time
+                )
+            },
+            CalendarError::UnknownAppointmentType(name) => {
+                write!(f, "Unknown appointment type: {}", name)
+            },
+            CalendarError::InvertedRange { from, to } => {
+                write!(f, "The `to` date {} is before the `from` date {}", to, from)
+            },
+            CalendarError::StaleVersion { expected, current } => {
+                write!(
+                    f,
+                    "The calendar changed since version {}, it's now at version {}",
+                    expected, current
+                )
+            },
+            CalendarError::DuplicateId => {
+                write!(f, "Another appointment already has the appointment's id")
+            },
+        }
+    }
+}
+
+impl std::error::Error for CalendarError {}
+
+/// Errors returned when building a working schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// The working hours window doesn't end after it starts
+    EmptyWindow { start: NaiveTime, end: NaiveTime },
+    /// The working hours window starts before the previous one
+    UnorderedWindows {
+        previous: (NaiveTime, NaiveTime),
+        next: (NaiveTime, NaiveTime),
+    },
+    /// The working hours window starts before the previous one ends
+    OverlappingWindows {
+        previous: (NaiveTime, NaiveTime),
+        next: (NaiveTime, NaiveTime),
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::EmptyWindow { start, end } => {
+                write!(
+                    f,
+                    "Working hours window {}-{} doesn't end after it starts",
+                    start, end
+                )
+            },
+            ScheduleError::UnorderedWindows { previous, next } => write!(
+                f,
+                "Working hours window {}-{} starts before the window {}-{}",
+                next.0, next.1, previous.0, previous.1
+            ),
+            ScheduleError::OverlappingWindows { previous, next } => write!(
+                f,
+                "Working hours window {}-{} overlaps with the window {}-{}",
+                next.0, next.1, previous.0, previous.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Errors returned when loading a clinic configuration file
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file can't be read
+    Io(io::Error),
+    /// The file is not a valid configuration
+    Parse(serde_json::Error),
+    /// The working schedule is invalid
+    Schedule(ScheduleError),
+    /// The slot size is not supported, only 15 minute time slots are
+    UnsupportedSlotSize(i64),
+    /// The duration of the appointment type is not a positive multiple of the
+    /// slot size
+    InvalidDuration(AppointmentType),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Can't read the configuration file: {}", e),
+            ConfigError::Parse(e) => write!(f, "Invalid configuration file: {}", e),
+            ConfigError::Schedule(e) => write!(f, "Invalid working schedule: {}", e),
+            ConfigError::UnsupportedSlotSize(minutes) => {
+                write!(
+                    f,
+                    "Time slots of {} minutes are not supported, only 15 minutes",
+                    minutes
+                )
+            },
+            ConfigError::InvalidDuration(appointment_type) => {
+                write!(
+                    f,
+                    "Duration of {} is not a positive multiple of 15 minutes",
+                    appointment_type.display_name()
+                )
+            },
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Schedule(e) => Some(e),
+            ConfigError::UnsupportedSlotSize(_) | ConfigError::InvalidDuration(_) => None,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Cache of the free time slots of a calendar
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{
+    appointment::{AppointmentType, Cancellation, DoctorsAppointment, DoctorsCalendar},
+    error::CalendarError,
+};
+
+/// Calendar whose free time slots are cached per appointment type and day, e.g.
+/// for a server answering availability queries
+///
+/// Extending a query to later days only computes the days that are not cached
+/// yet. Booking or cancelling an appointment through the cache invalidates the
+/// days it covers. Other changes, like closures of a shared clinic config or
+/// minimum notices passing as time goes by, are not tracked: call
+/// `invalidate_all` when they matter
+pub struct FreeSlotsCache {
+    calendar: DoctorsCalendar,
+    days: BTreeMap<(AppointmentType, NaiveDate), Vec<NaiveDateTime>>,
+    computed_days: usize,
+}
+
+impl FreeSlotsCache {
+    /// Create a new empty cache of the free time slots of the calendar
+    pub fn new(calendar: DoctorsCalendar) -> Self {
+        Self {
+            calendar,
+            days: BTreeMap::new(),
+            computed_days: 0,
+        }
+    }
+
+    /// Get the cached calendar
+    pub fn calendar(&self) -> &DoctorsCalendar {
+        &self.calendar
+    }
+
+    /// Get the number of days computed since the cache was created, including
+    /// days computed again after being invalidated
+    pub fn computed_days(&self) -> usize {
+        self.computed_days
+    }
+
+    /// Get the free time slots of the appointment type from the `from` date
+    /// until the `to` date, both included, in chronological order
+    ///
+    /// Only the days missing from the cache are computed
+    pub fn free_slots(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        appointment_type: AppointmentType,
+    ) -> Vec<NaiveDateTime> {
+        let mut free_slots = vec![];
+
+        for date in from.iter_days().take_while(|date| *date <= to) {
+            let day_slots = self
+                .days
+                .entry((appointment_type, date))
+                .or_insert_with(|| {
+                    self.computed_days += 1;
+
+                    self.calendar.free_slots(
+                        Some(date.and_time(NaiveTime::MIN)),
+                        Some(date.and_hms_opt(23, 59, 0).unwrap()),
+                        appointment_type,
+                    )
+                });
+
+            free_slots.extend(day_slots.iter().copied());
+        }
+
+        free_slots
+    }
+
+    /// Add an appointment to the calendar, invalidating the days it covers
+    pub fn add_appointment(
+        &mut self,
+        appointment: DoctorsAppointment,
+    ) -> Result<(), CalendarError> {
+        self.calendar.add_appointment(appointment.clone())?;
+        self.invalidate(appointment.date_time, appointment.end_time());
+
+        Ok(())
+    }
+
+    /// Cancel the appointment starting at the given date and time,
+    /// invalidating the days it covered
+    pub fn cancel_appointment(
+        &mut self,
+        date_time: NaiveDateTime,
+    ) -> Result<Cancellation, CalendarError> {
+        let cancellation = self.calendar.cancel_appointment(date_time)?;
+        self.invalidate(
+            cancellation.appointment.date_time,
+            cancellation.appointment.end_time(),
+        );
+
+        Ok(cancellation)
+    }
+
+    /// Invalidate the cached days of every appointment type overlapping with
+    /// the time from `start` until `end`
+    pub fn invalidate(&mut self, start: NaiveDateTime, end: NaiveDateTime) {
+        let first_day = start.date();
+        // The end is excluded, so an appointment ending at midnight doesn't
+        // invalidate the next day
+        let last_day = (end - Duration::nanoseconds(1)).date().max(first_day);
+
+        self.days
+            .retain(|(_, date), _| *date < first_day || *date > last_day);
+    }
+
+    /// Invalidate every cached day
+    pub fn invalidate_all(&mut self) {
+        self.days.clear();
+    }
+}
@@ -0,0 +1,98 @@
+//! Index of the time covered by the appointments, for overlap queries
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+
+use crate::appointment::DoctorsAppointment;
+
+/// Index of the appointments by the 15 minute time slots they cover
+///
+/// Every appointment is stored in each time slot it covers, so the
+/// appointments overlapping with a time period are found in `O(log n + k)`,
+/// however long they are. Appointments last a few time slots, which keeps the
+/// index small
+#[derive(Debug, Default, Clone)]
+pub(crate) struct IntervalIndex {
+    time_slots: BTreeMap<NaiveDateTime, Vec<DoctorsAppointment>>,
+}
+
+impl IntervalIndex {
+    /// Add an appointment to the index
+    pub(crate) fn insert(&mut self, appointment: &DoctorsAppointment) {
+        for time_slot in covered_time_slots(appointment) {
+            let appointments = self.time_slots.entry(time_slot).or_default();
+
+            // Keep the appointments of a time slot in order of their keys, so
+            // that equal appointments with different ids are kept apart
+            if let Err(index) =
+                appointments.binary_search_by_key(&appointment.key(), DoctorsAppointment::key)
+            {
+                appointments.insert(index, appointment.clone());
+            }
+        }
+    }
+
+    /// Remove an appointment from the index
+    pub(crate) fn remove(&mut self, appointment: &DoctorsAppointment) {
+        for time_slot in covered_time_slots(appointment) {
+            let Some(appointments) = self.time_slots.get_mut(&time_slot) else {
+                continue;
+            };
+
+            if let Ok(index) =
+                appointments.binary_search_by_key(&appointment.key(), DoctorsAppointment::key)
+            {
+                appointments.remove(index);
+            }
+
+            if appointments.is_empty() {
+                self.time_slots.remove(&time_slot);
+            }
+        }
+    }
+
+    /// Get the appointments overlapping with the time from `start` until
+    /// `end`, in chronological order
+    ///
+    /// Each appointment is returned once, at the first time slot it covers
+    /// within that time. The ones starting before `start` all share the first
+    /// time slot, whose appointments are in order, so the order holds
+    pub(crate) fn overlapping(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = &DoctorsAppointment> {
+        let first_time_slot = floor_to_time_slot(start);
+
+        self.time_slots
+            .range(first_time_slot..end.max(first_time_slot))
+            .flat_map(move |(time_slot, appointments)| {
+                appointments.iter().filter(move |appointment| {
+                    // Only return an appointment at the first time slot it
+                    // covers within the time period
+                    floor_to_time_slot(appointment.date_time).max(first_time_slot) == *time_slot
+                        && appointment.date_time < end
+                        && appointment.end_time() > start
+                })
+            })
+    }
+}
+
+/// Floor the date and time to its 15 minute time slot
+fn floor_to_time_slot(date_time: NaiveDateTime) -> NaiveDateTime {
+    let minutes_of_day = (date_time.time() - NaiveTime::MIN).num_minutes();
+
+    date_time.date().and_time(NaiveTime::MIN) + Duration::minutes(minutes_of_day / 15 * 15)
+}
+
+/// Get the 15 minute time slots the appointment covers, even partially
+fn covered_time_slots(appointment: &DoctorsAppointment) -> impl Iterator<Item = NaiveDateTime> {
+    let end = appointment.end_time();
+
+    std::iter::successors(
+        Some(floor_to_time_slot(appointment.date_time)),
+        |time_slot| Some(*time_slot + Duration::minutes(15)),
+    )
+    .take_while(move |time_slot| *time_slot < end)
+}
@@ -1,3 +1,11 @@
 pub mod appointment;
+pub mod audit;
+pub mod catalog;
 pub mod cli;
+pub mod config;
+pub mod error;
+pub mod free_slots_cache;
+mod interval_index;
+pub mod schedule;
 pub mod utils;
+pub mod waitlist;
@@ -1,29 +1,235 @@
-use appointment_booking::{appointment::*, cli::*, utils::*};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use appointment_booking::{appointment::*, cli::*, schedule::WorkingSchedule, utils::*};
+use clap::Parser;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
 
-fn main() {
     // Create a new doctor's calendar
     let mut calendar = DoctorsCalendar::new();
 
+    // Load the working schedule and the existing appointments, if a calendar
+    // file is given
+    if let Some(path) = args.calendar.as_deref() {
+        if let Err(e) = load_schedule(&mut calendar, path) {
+            eprintln!("Failed to load working schedule: {}", e);
+            return ExitCode::FAILURE;
+        }
+
+        if let Err(e) = load_calendar(&mut calendar, path) {
+            eprintln!("Failed to load calendar: {}", e);
+            return ExitCode::FAILURE;
+        }
+
+        // The appointments are kept when the working hours change, so warn
+        // about the ones the saved schedule leaves out
+        for appointment in calendar.appointments_outside_schedule() {
+            eprintln!(
+                "Warning: appointment outside the working hours: {}, {}",
+                format_slot(appointment.date_time, DEFAULT_SLOT_FORMAT),
+                appointment.appointment_type.display_name()
+            );
+        }
+    }
+
+    let output_format = args.output_format();
+
+    match args.command {
+        Some(command) => {
+            if let Err(e) = run_command(
+                &mut calendar,
+                command,
+                args.calendar.as_deref(),
+                output_format,
+            ) {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => {
+            run_interactive(&mut calendar);
+
+            if let Some(path) = args.calendar.as_deref() {
+                if let Err(e) = save_calendar(&calendar, path) {
+                    eprintln!("Failed to save calendar: {}", e);
+                    return ExitCode::FAILURE;
+                }
+
+                if let Err(e) = save_schedule(&calendar, path) {
+                    eprintln!("Failed to save working schedule: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        },
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Load the appointments from the calendar file, as they were saved, even
+/// outside the working hours. A missing file is an empty calendar
+fn load_calendar(calendar: &mut DoctorsCalendar, path: &Path) -> io::Result<()> {
+    match File::open(path) {
+        Ok(file) => calendar.restore_jsonl(BufReader::new(file)).map(|_| ()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Save the appointments to the calendar file
+fn save_calendar(calendar: &DoctorsCalendar, path: &Path) -> io::Result<()> {
+    calendar.export_jsonl(BufWriter::new(File::create(path)?))
+}
+
+/// Get the path of the working schedule file stored alongside the calendar
+/// file, e.g. "calendar.schedule.json" for "calendar.jsonl"
+fn schedule_path(path: &Path) -> PathBuf {
+    path.with_extension("schedule.json")
+}
+
+/// Load the working schedule stored alongside the calendar file. A missing
+/// file keeps the default schedule
+fn load_schedule(calendar: &mut DoctorsCalendar, path: &Path) -> io::Result<()> {
+    match File::open(schedule_path(path)) {
+        Ok(file) => {
+            let schedule: WorkingSchedule = serde_json::from_reader(BufReader::new(file))?;
+            calendar.set_schedule(schedule);
+
+            Ok(())
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Save the working schedule alongside the calendar file
+fn save_schedule(calendar: &DoctorsCalendar, path: &Path) -> io::Result<()> {
+    let file = BufWriter::new(File::create(schedule_path(path))?);
+
+    Ok(serde_json::to_writer_pretty(file, calendar.schedule())?)
+}
+
+/// Run a non-interactive command, printing the results in the given output
+/// format
+fn run_command(
+    calendar: &mut DoctorsCalendar,
+    command: Command,
+    path: Option<&Path>,
+    output_format: OutputFormat,
+) -> Result<(), String> {
+    // Reject inverted ranges instead of silently returning no results
+    if let Command::Booked {
+        from: Some(from),
+        to: Some(to),
+    }
+    | Command::FreeSlots {
+        from: Some(from),
+        to: Some(to),
+        ..
+    } = command
+    {
+        check_range(from, to).map_err(|e| e.to_string())?;
+    }
+
+    match command {
+        Command::Add {
+            appointment_type,
+            at,
+        } => {
+            // Create new appointment and add it to the calendar
+            let appointment = DoctorsAppointment::new(at, appointment_type);
+            calendar.add_appointment(appointment.clone()).map_err(|e| {
+                // Suggest the next free time slots of the same type
+                let suggestions = calendar
+                    .next_available_slots(at, appointment_type, 3)
+                    .iter()
+                    .map(|slot| format_slot(*slot, DEFAULT_SLOT_FORMAT))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                format!(
+                    "Failed to add appointment: {}\nNext available: {}",
+                    e, suggestions
+                )
+            })?;
+
+            // Persist the new appointment
+            if let Some(path) = path {
+                save_calendar(calendar, path)
+                    .map_err(|e| format!("Failed to save calendar: {}", e))?;
+            }
+
+            print_appointments(&[appointment], output_format);
+        },
+        Command::Booked { from, to } => {
+            print_appointments(&calendar.booked_appointments(from, to), output_format);
+        },
+        Command::FreeSlots {
+            appointment_type,
+            from,
+            to,
+            optimized,
+        } => {
+            let slots = if optimized {
+                calendar.free_slots_optimized(from, to, appointment_type)
+            } else {
+                calendar.free_slots(from, to, appointment_type)
+            };
+
+            print_slots(&slots, output_format);
+        },
+    }
+
+    Ok(())
+}
+
+/// Run the interactive menus until the user quits
+fn run_interactive(calendar: &mut DoctorsCalendar) {
     // Set the default `from` and `to` dates
     let mut from = now_next_15_mark();
     let mut to = end_of_day();
 
     loop {
-        println!("Current `from` date: {}", from);
-        println!("Current `to` date: {}", to);
+        println!(
+            "Current `from` date: {}",
+            format_slot(from, DEFAULT_SLOT_FORMAT)
+        );
+        println!(
+            "Booked: {} appointments, {:.1}% utilization in range.",
+            calendar.booked_appointments(Some(from), Some(to)).len(),
+            calendar.utilization(from, to)
+        );
+        println!(
+            "Current `to` date: {}",
+            format_slot(to, DEFAULT_SLOT_FORMAT)
+        );
         println!();
 
         match main_menu() {
             Action::SetFromDate => {
                 // Display the menu and get date from user
                 if let Some(date) = set_from_date_menu() {
-                    from = date;
+                    // Keep the previous date if the range would be inverted
+                    match check_range(date, to) {
+                        Ok(()) => from = date,
+                        Err(e) => println!("Failed to set `from` date: {}", e),
+                    }
                 }
             },
             Action::SetToDate => {
                 // Display the menu and get date from user
                 if let Some(date) = set_to_date_menu() {
-                    to = date;
+                    // Keep the previous date if the range would be inverted
+                    match check_range(from, date) {
+                        Ok(()) => to = date,
+                        Err(e) => println!("Failed to set `to` date: {}", e),
+                    }
                 }
             },
             Action::FillRandom => {
@@ -38,13 +244,7 @@ fn main() {
                 let booked_appointments = calendar.booked_appointments(Some(from), Some(to));
 
                 // List all booked appointments
-                booked_appointments.iter().for_each(|appointment| {
-                    println!(
-                        "Date: {}, Type: {}",
-                        appointment.date_time,
-                        appointment.appointment_type.display_name()
-                    );
-                });
+                print_appointments(&booked_appointments, OutputFormat::Human);
             },
             Action::AddNewAppointment => {
                 // Display the menu and get appointment type and date from user
@@ -60,6 +260,13 @@ fn main() {
                     // Handle the result
                     if let Err(e) = result {
                         println!("Failed to add appointment: {}", e);
+
+                        // Suggest the next free time slots of the same type
+                        println!("Next available time slots:");
+                        print_slots(
+                            &calendar.next_available_slots(date, appointment_type, 3),
+                            OutputFormat::Human,
+                        );
                     } else {
                         println!("Appointment added successfully");
                     }
@@ -74,7 +281,7 @@ fn main() {
 
                 // Display time slots
                 println!("Free time slots:");
-                slots.iter().for_each(|slot| println!("{}", slot));
+                print_slots(&slots, OutputFormat::Human);
             },
             Action::ListOptimizedFreeTimeSlots => {
                 // Display the menu and get appointment type from user
@@ -85,7 +292,26 @@ fn main() {
 
                 // Display optimized time slots
                 println!("Optimized free time slots:");
-                slots.iter().for_each(|slot| println!("{}", slot));
+                print_slots(&slots, OutputFormat::Human);
+            },
+            Action::DayAgenda => {
+                // Display the agenda of the first day of the range
+                println!("Agenda of {}:", from.format("%A %Y-%m-%d"));
+                print!("{}", calendar.render_day(from.date()));
+            },
+            Action::EditSchedule => {
+                // Display the menu and get the new schedule from user
+                if let Some(schedule) = edit_schedule_menu(calendar.schedule()) {
+                    calendar.set_schedule(schedule);
+                    println!("Working schedule updated");
+
+                    // Warn about the appointments the new schedule leaves out
+                    let outside = calendar.appointments_outside_schedule();
+                    if !outside.is_empty() {
+                        println!("Appointments outside the new working hours:");
+                        print_appointments(&outside, OutputFormat::Human);
+                    }
+                }
             },
             Action::Quit => {
                 println!("Exiting...");
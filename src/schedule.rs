@@ -0,0 +1,448 @@
+//! Working schedule of the practice
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    appointment::AppointmentType,
+    error::{CalendarError, ScheduleError},
+};
+
+/// Working hours and working days of the practice
+///
+/// A working day is split in one or more working hours windows. The gaps
+/// between the windows are breaks, e.g. a lunch break.
+///
+/// Single dates can be extended with one-off working hours windows, e.g. when
+/// the doctor stays late on one day.
+///
+/// The schedule is serialized as its windows, days and one-off windows, and
+/// validated like `WorkingScheduleBuilder::build` when deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "WorkingScheduleBuilder")]
+pub struct WorkingSchedule {
+    windows: Vec<(NaiveTime, NaiveTime)>,
+    days: Vec<Weekday>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    extra_windows: BTreeMap<NaiveDate, Vec<(NaiveTime, NaiveTime)>>,
+}
+
+impl Default for WorkingSchedule {
+    /// Monday to Friday, 8:00 to 12:00 and 13:00 to 17:00
+    fn default() -> Self {
+        Self::with_break(
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            Duration::hours(1),
+        )
+        .unwrap()
+    }
+}
+
+impl WorkingSchedule {
+    /// Create a builder for a custom working schedule
+    pub fn builder() -> WorkingScheduleBuilder {
+        WorkingScheduleBuilder::default()
+    }
+
+    /// Create a Monday to Friday schedule with a single working hours window
+    /// and no break
+    pub fn continuous(start: NaiveTime, end: NaiveTime) -> Result<Self, ScheduleError> {
+        Self::builder().window(start, end).build()
+    }
+
+    /// Create a Monday to Friday schedule from `start` to `end`, with a break
+    /// of the given length starting at `break_start`
+    pub fn with_break(
+        start: NaiveTime,
+        end: NaiveTime,
+        break_start: NaiveTime,
+        break_length: Duration,
+    ) -> Result<Self, ScheduleError> {
+        Self::builder()
+            .window(start, break_start)
+            .window(break_start + break_length, end)
+            .build()
+    }
+
+    /// Change the working days, keeping the working hours windows
+    ///
+    /// Any set of weekdays can be used, e.g. Sunday to Thursday for a practice
+    /// closed on Friday and Saturday. No days at all is valid too, e.g. for a
+    /// doctor on leave: only one-off windows are then working time
+    pub fn with_working_days(mut self, days: &[Weekday]) -> Self {
+        self.days = days.to_vec();
+        self
+    }
+
+    /// Add a one-off working hours window to a single date
+    ///
+    /// The window is merged with the regular windows of the date, so extending
+    /// a day from 17:00 to 18:00 makes the afternoon run until 18:00. A
+    /// non-working day only gets the one-off windows
+    pub fn extend_day(
+        &mut self,
+        date: NaiveDate,
+        (start, end): (NaiveTime, NaiveTime),
+    ) -> Result<(), ScheduleError> {
+        if end <= start {
+            return Err(ScheduleError::EmptyWindow { start, end });
+        }
+
+        self.extra_windows
+            .entry(date)
+            .or_default()
+            .push((start, end));
+
+        Ok(())
+    }
+
+    /// Get the working hours windows of a working day
+    pub fn windows(&self) -> &[(NaiveTime, NaiveTime)] {
+        &self.windows
+    }
+
+    /// Get the working hours windows of the given date, including its one-off
+    /// windows
+    ///
+    /// Overlapping or touching windows are merged. Dates without one-off
+    /// windows have the regular windows, whether they are working days or not
+    pub fn windows_on(&self, date: NaiveDate) -> Vec<(NaiveTime, NaiveTime)> {
+        let Some(extra_windows) = self.extra_windows.get(&date) else {
+            return self.windows.clone();
+        };
+
+        let mut windows = extra_windows.clone();
+
+        if self.days.contains(&date.weekday()) {
+            windows.extend(self.windows.iter().copied());
+        }
+
+        windows.sort();
+
+        let mut merged: Vec<(NaiveTime, NaiveTime)> = vec![];
+
+        for (start, end) in windows {
+            match merged.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        merged
+    }
+
+    /// Get the length of the longest working hours window, including the
+    /// one-off windows
+    pub fn longest_window(&self) -> Duration {
+        self.extra_windows
+            .keys()
+            .flat_map(|date| self.windows_on(*date))
+            .chain(self.windows.iter().copied())
+            .map(|(start, end)| end - start)
+            .max()
+            .unwrap_or(Duration::zero())
+    }
+
+    /// Get the working days
+    pub fn days(&self) -> &[Weekday] {
+        &self.days
+    }
+
+    /// Check if the date is a working day, or has one-off working hours
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        self.days.contains(&date.weekday()) || self.extra_windows.contains_key(&date)
+    }
+
+    /// Check if the time is within the regular working hours
+    pub fn is_working_hour(&self, time: NaiveTime) -> bool {
+        window_index(&self.windows, time).is_some()
+    }
+
+    /// Check if the date and time is within the working hours of a working day,
+    /// including its one-off windows
+    pub fn is_working_day_and_hour(&self, date_time: NaiveDateTime) -> bool {
+        self.is_working_day(date_time.date())
+            && window_index(&self.windows_on(date_time.date()), date_time.time()).is_some()
+    }
+
+    /// Get the 15 minute time slots of a working day, in order
+    pub fn day_time_slots(&self, date: NaiveDate) -> Vec<NaiveDateTime> {
+        self.windows_on(date)
+            .iter()
+            .flat_map(|(start, end)| {
+                let mut time_slots = vec![];
+                let mut current = date.and_time(*start);
+
+                while current < date.and_time(*end) {
+                    time_slots.push(current);
+                    current += Duration::minutes(15);
+                }
+
+                time_slots
+            })
+            .collect()
+    }
+
+    /// Get the next working date and time
+    ///
+    /// This function gets the next 15 minute time slot that is within the
+    /// working hours, after advancing by the appointment type's duration (or
+    /// 15 minutes if not provided). Returns `NaiveDateTime::MAX` if the
+    /// schedule has no working time at all
+    pub fn next_working_datetime(
+        &self,
+        date_time: NaiveDateTime,
+        appointment_type: Option<AppointmentType>,
+    ) -> NaiveDateTime {
+        // Round to the last 15 minute time
+        let current = date_time.date().and_time(
+            NaiveTime::from_hms_opt(
+                date_time.time().hour(),
+                (date_time.time().minute() / 15) * 15,
+                0,
+            )
+            .unwrap_or(date_time.time()),
+        );
+
+        // Get the time slot duration. If the appointment type is not provided, use the
+        // default time slot duration of 15 minutes
+        let time_slot_duration = if let Some(appointment_type) = appointment_type {
+            appointment_type.duration()
+        } else {
+            // Default time slot duration is 15 minutes
+            Duration::minutes(15)
+        };
+
+        // Append time slot duration to the current time, which can't go past
+        // the sentinel of a schedule without working time
+        let Some(current) = current.checked_add_signed(time_slot_duration) else {
+            return NaiveDateTime::MAX;
+        };
+
+        if self.is_working_day_and_hour(current) {
+            return current;
+        }
+
+        // Check if a later working hours window starts on the same working day
+        if self.is_working_day(current.date()) {
+            if let Some((start, _)) = self
+                .windows_on(current.date())
+                .into_iter()
+                .find(|(start, _)| current.time() < *start)
+            {
+                return current.date().and_time(start);
+            }
+        }
+
+        // Set the time to the start of the working hours of the next working day
+        (1..=7)
+            .filter_map(|days| current.date().checked_add_signed(Duration::days(days)))
+            .filter(|date| self.is_working_day(*date))
+            .find_map(|date| {
+                self.windows_on(date)
+                    .first()
+                    .map(|(start, _)| date.and_time(*start))
+            })
+            .unwrap_or(NaiveDateTime::MAX)
+    }
+
+    /// Iterate over the working 15 minute time slots from `from` up to, but
+    /// excluding, `to`
+    ///
+    /// `from` itself is the first time slot if it's within the working hours
+    pub fn working_slots(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        let first = if self.is_working_day_and_hour(from) {
+            from
+        } else {
+            self.next_working_datetime(from, None)
+        };
+
+        // Stop advancing past `to`, as a schedule without working time has no
+        // time slot after the `NaiveDateTime::MAX` sentinel
+        std::iter::successors(Some(first), move |current| {
+            (*current < to).then(|| self.next_working_datetime(*current, None))
+        })
+        .take_while(move |current| *current < to)
+    }
+
+    /// Snap the date and time into a working 15 minute time slot
+    ///
+    /// Unlike `next_working_datetime`, which always advances by a time slot
+    /// before looking for working hours, this returns the date and time itself
+    /// if it's already a valid working time slot. Times that are not on a 15
+    /// minute mark are rounded up to the next one. Returns `None` if no working
+    /// time slot is found within a week
+    pub fn snap_to_working_slot(&self, date_time: NaiveDateTime) -> Option<NaiveDateTime> {
+        // Round up to the next 15 minute time, unless already on one
+        let rounded = date_time.date().and_time(
+            NaiveTime::from_hms_opt(
+                date_time.time().hour(),
+                (date_time.time().minute() / 15) * 15,
+                0,
+            )
+            .unwrap_or(date_time.time()),
+        );
+        let mut current = if rounded == date_time {
+            rounded
+        } else {
+            rounded + Duration::minutes(15)
+        };
+
+        let limit = current + Duration::weeks(1);
+
+        // Advance until a working time slot is found
+        while current < limit {
+            if self.is_working_day_and_hour(current) {
+                return Some(current);
+            }
+
+            current = self.next_working_datetime(current, None);
+        }
+
+        None
+    }
+
+    /// Get the end time of an appointment starting at the given date and time
+    ///
+    /// The appointment must fit within a single working hours window. An
+    /// appointment running into a break returns `CalendarError::SpansBreak`,
+    /// and one running past the end of the working day returns
+    /// `CalendarError::OutsideWorkingHours`
+    pub fn end_time_respecting_breaks(
+        &self,
+        date_time: NaiveDateTime,
+        duration: Duration,
+    ) -> Result<NaiveDateTime, CalendarError> {
+        if !self.is_working_day(date_time.date()) {
+            return Err(CalendarError::OutsideWorkingHours);
+        }
+
+        let windows = self.windows_on(date_time.date());
+
+        // Find the working hours window the appointment starts in
+        let window_index =
+            window_index(&windows, date_time.time()).ok_or(CalendarError::OutsideWorkingHours)?;
+
+        let end_time = date_time + duration;
+
+        // Check if the appointment ends within the same window
+        if end_time <= date_time.date().and_time(windows[window_index].1) {
+            return Ok(end_time);
+        }
+
+        // Check if there is another window after the current one
+        if window_index + 1 < windows.len() {
+            Err(CalendarError::SpansBreak)
+        } else {
+            Err(CalendarError::OutsideWorkingHours)
+        }
+    }
+}
+
+/// Get the index of the working hours window containing the time
+fn window_index(windows: &[(NaiveTime, NaiveTime)], time: NaiveTime) -> Option<usize> {
+    windows
+        .iter()
+        .position(|(start, end)| time >= *start && time < *end)
+}
+
+/// Builder for a custom `WorkingSchedule`
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkingScheduleBuilder {
+    windows: Vec<(NaiveTime, NaiveTime)>,
+    days: Vec<Weekday>,
+    #[serde(default)]
+    extra_windows: BTreeMap<NaiveDate, Vec<(NaiveTime, NaiveTime)>>,
+}
+
+impl Default for WorkingScheduleBuilder {
+    /// No working hours windows, Monday to Friday
+    fn default() -> Self {
+        Self {
+            windows: vec![],
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            extra_windows: BTreeMap::new(),
+        }
+    }
+}
+
+impl WorkingScheduleBuilder {
+    /// Add a working hours window from `start` to `end`
+    pub fn window(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.windows.push((start, end));
+        self
+    }
+
+    /// Set the working days, Monday to Friday by default
+    pub fn working_days(mut self, days: &[Weekday]) -> Self {
+        self.days = days.to_vec();
+        self
+    }
+
+    /// Build the working schedule
+    ///
+    /// The working hours windows must be added in order, and must not overlap.
+    /// Windows can end when the next one starts. A schedule without windows or
+    /// without working days is valid, and has no working time slots, so
+    /// `next_working_datetime` returns `NaiveDateTime::MAX`
+    pub fn build(self) -> Result<WorkingSchedule, ScheduleError> {
+        for (start, end) in self.windows.iter() {
+            if end <= start {
+                return Err(ScheduleError::EmptyWindow {
+                    start: *start,
+                    end: *end,
+                });
+            }
+        }
+
+        for pair in self.windows.windows(2) {
+            let (previous, next) = (pair[0], pair[1]);
+
+            if next.0 < previous.0 {
+                return Err(ScheduleError::UnorderedWindows { previous, next });
+            }
+
+            if next.0 < previous.1 {
+                return Err(ScheduleError::OverlappingWindows { previous, next });
+            }
+        }
+
+        for (start, end) in self.extra_windows.values().flatten() {
+            if end <= start {
+                return Err(ScheduleError::EmptyWindow {
+                    start: *start,
+                    end: *end,
+                });
+            }
+        }
+
+        Ok(WorkingSchedule {
+            windows: self.windows,
+            days: self.days,
+            extra_windows: self.extra_windows,
+        })
+    }
+}
+
+impl TryFrom<WorkingScheduleBuilder> for WorkingSchedule {
+    type Error = ScheduleError;
+
+    /// Same as `WorkingScheduleBuilder::build`
+    fn try_from(builder: WorkingScheduleBuilder) -> Result<Self, Self::Error> {
+        builder.build()
+    }
+}
@@ -1,6 +1,41 @@
 //! Utility functions for the application
 
-use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, Timelike};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::CalendarError;
+
+/// Default format used to display time slots, without seconds
+pub const DEFAULT_SLOT_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Source of the current date and time
+///
+/// Used instead of reading the wall clock directly, so that a fixed time can
+/// be injected in tests or servers
+pub trait Clock: Send + Sync {
+    /// Return the current date and time
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// Clock reading the system's local time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+/// Clock that always returns the same date and time
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub NaiveDateTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveDateTime {
+        self.0
+    }
+}
 
 /// Return a NaiveDateTime for the next 15 minute mark time from passed local
 /// time
@@ -27,6 +62,22 @@ pub fn next_15_mark(date: DateTime<Local>) -> NaiveDateTime {
         .naive_utc()
 }
 
+/// Return the next 15 minute mark time from the passed date and time
+///
+/// i.e. 18:12 => 18:15, 18:15 => 18:30
+pub fn next_15_mark_from(date: NaiveDateTime) -> NaiveDateTime {
+    // Drop the seconds and round down to the last 15 minute mark
+    let rounded = date
+        .with_minute((date.minute() / 15) * 15)
+        .unwrap_or_default()
+        .with_second(0)
+        .unwrap_or_default()
+        .with_nanosecond(0)
+        .unwrap_or_default();
+
+    rounded + Duration::minutes(15)
+}
+
 /// Return a NaiveDateTime for the next 15 minute time from the current time
 pub fn now_next_15_mark() -> NaiveDateTime {
     // Get the current time
@@ -52,18 +103,66 @@ pub fn end_of_day() -> NaiveDateTime {
 }
 
 
-/// Return a NaiveDateTime for the end of the day this Friday
+/// Return a NaiveDateTime for the end of the working day this Friday
 pub fn end_of_week() -> NaiveDateTime {
-    // Get the current time
-    let now = Local::now();
+    end_of_week_from(SystemClock.now())
+}
 
-    // Get the end of the day
-    let end_of_day = now.with_hour(23).unwrap().with_minute(59).unwrap();
+/// Return a NaiveDateTime for the end of the working day this Friday, relative
+/// to the passed date and time
+///
+/// Once the working week is over, i.e. after Friday 17:00 or on a weekend,
+/// this returns the end of the working day next Friday
+pub fn end_of_week_from(date: NaiveDateTime) -> NaiveDateTime {
+    // Get the number of days until Friday, negative on the weekend
+    let days_until_friday =
+        Weekday::Fri.num_days_from_monday() as i64 - date.weekday().num_days_from_monday() as i64;
+
+    // Get the NaiveDateTime for the end of the working day this Friday
+    let end_of_week = (date.date() + Duration::days(days_until_friday))
+        .and_time(NaiveTime::from_hms_opt(17, 0, 0).unwrap());
+
+    if end_of_week < date {
+        end_of_week + Duration::weeks(1)
+    } else {
+        end_of_week
+    }
+}
+
+/// Check that the `to` date of a range is not before its `from` date
+pub fn check_range(from: NaiveDateTime, to: NaiveDateTime) -> Result<(), CalendarError> {
+    if to < from {
+        return Err(CalendarError::InvertedRange { from, to });
+    }
+
+    Ok(())
+}
+
+/// Format a time slot with the given `strftime` format string
+///
+/// i.e. `format_slot(date, DEFAULT_SLOT_FORMAT)` => "2024-02-01 08:00"
+pub fn format_slot(date: NaiveDateTime, fmt: &str) -> String {
+    date.format(fmt).to_string()
+}
 
-    // Get the number of days until the end of the week
-    let end_of_week = end_of_day.weekday().num_days_from_monday();
+/// Serialize an optional `Duration` as a whole number of minutes
+///
+/// For use with `#[serde(with = "optional_duration_minutes")]`
+pub mod optional_duration_minutes {
+    use super::*;
 
-    // Get the NaiveDateTime for the end of the day this Friday
-    let end_of_weekdays = end_of_day + Duration::days(7 - end_of_week as i64);
-    end_of_weekdays.naive_utc()
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        duration
+            .map(|duration| duration.num_minutes())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<i64>::deserialize(deserializer)?.map(Duration::minutes))
+    }
 }
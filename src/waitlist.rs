@@ -0,0 +1,78 @@
+//! Waitlist of patients waiting for a free time slot
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::appointment::{AppointmentType, Patient};
+
+/// Patients waiting for an appointment of a given type on a given date
+#[derive(Debug, Default, Clone)]
+pub struct Waitlist {
+    entries: BTreeMap<(NaiveDate, AppointmentType), Vec<Patient>>,
+}
+
+impl Waitlist {
+    /// Create a new empty waitlist
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a patient to the waitlist for the given date and appointment type
+    pub fn add(&mut self, date: NaiveDate, appointment_type: AppointmentType, patient: Patient) {
+        self.entries
+            .entry((date, appointment_type))
+            .or_default()
+            .push(patient);
+    }
+
+    /// Remove a patient from the waitlist for the given date and appointment
+    /// type. Returns whether the patient was waitlisted
+    pub fn remove(
+        &mut self,
+        date: NaiveDate,
+        appointment_type: AppointmentType,
+        patient: &Patient,
+    ) -> bool {
+        let Some(patients) = self.entries.get_mut(&(date, appointment_type)) else {
+            return false;
+        };
+
+        let Some(index) = patients.iter().position(|waiting| waiting == patient) else {
+            return false;
+        };
+        patients.remove(index);
+
+        // Don't keep empty entries around
+        if patients.is_empty() {
+            self.entries.remove(&(date, appointment_type));
+        }
+
+        true
+    }
+
+    /// Get the waitlisted patients for the given date and appointment type, in
+    /// the order they were added
+    pub fn patients(&self, date: NaiveDate, appointment_type: AppointmentType) -> &[Patient] {
+        self.entries
+            .get(&(date, appointment_type))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Get the waitlisted appointment types and patients for the given date
+    pub fn for_date(&self, date: NaiveDate) -> Vec<(AppointmentType, &Patient)> {
+        self.entries
+            .iter()
+            .filter(|((entry_date, _), _)| *entry_date == date)
+            .flat_map(|((_, appointment_type), patients)| {
+                patients.iter().map(|patient| (*appointment_type, patient))
+            })
+            .collect()
+    }
+
+    /// Check if the waitlist is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -3,10 +3,28 @@ use appointment_booking::appointment::*;
 
 #[cfg(test)]
 mod tests {
-    use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use appointment_booking::{
+        audit::{AuditEntry, AuditOperation},
+        catalog::AppointmentCatalog,
+        config::ClinicConfig,
+        error::CalendarError,
+        schedule::WorkingSchedule,
+        utils::FixedClock,
+    };
+    use chrono::{Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
 
     use super::*;
 
+    /// Get the given time on Thursday 2024-02-01, the day most tests book on
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+        )
+    }
+
     #[test]
     // Test the is_working_day function
     fn test_is_working_day() {
@@ -256,7 +274,7 @@ mod tests {
     #[test]
     fn test_new_doctors_calendar() {
         let calendar = DoctorsCalendar::new();
-        assert!(calendar.appointments.is_empty());
+        assert!(calendar.is_empty());
     }
 
     // Test the add_appointment function
@@ -274,7 +292,7 @@ mod tests {
 
         let result = calendar.add_appointment(appointment);
         assert!(result.is_ok());
-        assert_eq!(calendar.appointments.len(), 1);
+        assert_eq!(calendar.len(), 1);
 
         let appointment = DoctorsAppointment::new(
             NaiveDateTime::new(
@@ -286,7 +304,7 @@ mod tests {
 
         let result = calendar.add_appointment(appointment);
         assert!(result.is_err());
-        assert_eq!(calendar.appointments.len(), 1);
+        assert_eq!(calendar.len(), 1);
     }
 
     // Test the booked_appointments function
@@ -302,7 +320,7 @@ mod tests {
             AppointmentType::DentalCheckUp,
         );
 
-        let _ = calendar.add_appointment(appointment);
+        let _ = calendar.add_appointment(appointment.clone());
 
         let booked_appointments = calendar.booked_appointments(None, None);
         assert_eq!(booked_appointments.len(), 1);
@@ -487,7 +505,6 @@ mod tests {
         );
         calendar.add_appointment(appointment).unwrap();
 
-
         let from = NaiveDateTime::new(
             NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
             NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
@@ -638,7 +655,6 @@ mod tests {
         );
         calendar.add_appointment(appointment).unwrap();
 
-
         let from = NaiveDateTime::new(
             NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
             NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
@@ -809,7 +825,6 @@ mod tests {
 
         assert_eq!(booked_appointments.len(), 32);
 
-
         let mut calendar = DoctorsCalendar::new();
 
         calendar.fill_random(from, to, AppointmentType::DentalCheckUp, 20);
@@ -836,4 +851,4926 @@ mod tests {
 
         assert_eq!(booked_appointments.len(), 12);
     }
+
+    // Test the free_slots defaults when `from` and `to` are not provided
+    #[test]
+    fn test_free_slots_default_range() {
+        // Thursday morning
+        let now = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 5, 0).unwrap(),
+        );
+        let calendar = DoctorsCalendar::with_clock(Arc::new(FixedClock(now)));
+
+        // From 08:15 on Thursday until the end of the week
+        let free_slots_urg =
+            calendar.free_slots(None, None, AppointmentType::UrgentDentalAppointment);
+        assert_eq!(
+            free_slots_urg[0],
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 15, 0).unwrap()
+            )
+        );
+        assert_eq!(
+            free_slots_urg,
+            calendar.free_slots(
+                Some(free_slots_urg[0]),
+                Some(free_slots_urg[free_slots_urg.len() - 1]),
+                AppointmentType::UrgentDentalAppointment
+            )
+        );
+
+        let free_slots_optimized =
+            calendar.free_slots_optimized(None, None, AppointmentType::DentalCheckUp);
+        assert_eq!(
+            free_slots_optimized[0],
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 15, 0).unwrap()
+            )
+        );
+    }
+
+    // Test the find_by_patient function
+    #[test]
+    fn test_find_by_patient() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        )
+        .with_patient(Patient::new("Jane Doe", "555-0100"));
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        )
+        .with_patient(Patient::new("Jane Doe", "555-0100"));
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        )
+        .with_patient(Patient::new("John Smith", "555-0199"));
+        calendar.add_appointment(appointment).unwrap();
+
+        // Appointment without patient info
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        // Case-insensitive name match, in chronological order
+        let found = calendar.find_by_patient("jane");
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            found[0].date_time,
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+            )
+        );
+        assert_eq!(
+            found[1].date_time,
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+            )
+        );
+
+        // Exact phone match
+        let found = calendar.find_by_patient("555-0199");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].patient.as_ref().unwrap().name, "John Smith");
+
+        // Partial phone numbers don't match
+        assert!(calendar.find_by_patient("555").is_empty());
+        assert!(calendar.find_by_patient("").is_empty());
+    }
+
+    // Test the day_bitmap function
+    #[test]
+    fn test_day_bitmap() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let bitmap = calendar.day_bitmap(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        assert_eq!(bitmap.len(), 32);
+        assert!(bitmap.iter().all(|free| *free));
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let bitmap = calendar.day_bitmap(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        assert_eq!(bitmap.len(), 32);
+        assert!(!bitmap[0]);
+        assert!(!bitmap[1]);
+        assert!(bitmap[2]);
+        // First afternoon time slot
+        assert!(!bitmap[16]);
+        assert!(bitmap[17]);
+        assert_eq!(bitmap.iter().filter(|free| **free).count(), 29);
+
+        // Saturday
+        let bitmap = calendar.day_bitmap(NaiveDate::from_ymd_opt(2024, 2, 3).unwrap());
+        assert_eq!(bitmap.len(), 32);
+        assert!(bitmap.iter().all(|free| !*free));
+    }
+
+    // Test the add_block and remove_block functions
+    #[test]
+    fn test_blocks() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+        );
+
+        let free_slots_urg = calendar.free_slots(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(free_slots_urg.len(), 32);
+
+        let block = Block::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+            ),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+            ),
+            "Admin",
+        );
+        calendar.add_block(block.clone());
+
+        let free_slots_urg = calendar.free_slots(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(free_slots_urg.len(), 28);
+        assert_eq!(calendar.available_single_time_slots(from, to).len(), 28);
+
+        // Blocks are not appointments
+        assert!(calendar
+            .booked_appointments(Some(from), Some(to))
+            .is_empty());
+
+        // Appointments can't be added over a block
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 45, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        assert!(calendar.add_appointment(appointment.clone()).is_err());
+
+        assert!(calendar.remove_block(&block));
+        assert!(!calendar.remove_block(&block));
+
+        let free_slots_urg = calendar.free_slots(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(free_slots_urg.len(), 32);
+        assert!(calendar.add_appointment(appointment).is_ok());
+    }
+
+    // Test the calculate_end_time_respecting_breaks function
+    #[test]
+    fn test_calculate_end_time_respecting_breaks() {
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(10, 30, 0).unwrap(),
+        );
+        let end_time =
+            date.calculate_end_time_respecting_breaks(AppointmentType::ImplantConsultation);
+        assert_eq!(
+            end_time,
+            Ok(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(12, 0, 0).unwrap()
+            ))
+        );
+
+        // Naive end of 13:00 spans the break
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(11, 30, 0).unwrap(),
+        );
+        assert_eq!(
+            date.calculate_end_time(AppointmentType::ImplantConsultation),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+            )
+        );
+        assert_eq!(
+            date.calculate_end_time_respecting_breaks(AppointmentType::ImplantConsultation),
+            Err(CalendarError::SpansBreak)
+        );
+
+        // Past the end of the working day
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(16, 45, 0).unwrap(),
+        );
+        assert_eq!(
+            date.calculate_end_time_respecting_breaks(AppointmentType::DentalCheckUp),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+
+        // Starting in the break
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+        );
+        assert_eq!(
+            date.calculate_end_time_respecting_breaks(AppointmentType::UrgentDentalAppointment),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+    }
+
+    // Test the cancel_appointment function surfaces waitlisted patients
+    #[test]
+    fn test_cancel_appointment_waitlist() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment.clone()).unwrap();
+
+        // Rest of the morning is blocked, so an implant can't fit in the freed time
+        calendar.add_block(Block::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+            ),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            ),
+            "Surgery",
+        ));
+
+        let jane = Patient::new("Jane Doe", "555-0100");
+        calendar.waitlist.add(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            AppointmentType::DentalCheckUp,
+            jane.clone(),
+        );
+        calendar.waitlist.add(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            AppointmentType::ImplantConsultation,
+            Patient::new("John Smith", "555-0199"),
+        );
+        calendar.waitlist.add(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            AppointmentType::DentalCheckUp,
+            Patient::new("Bob Brown", "555-0142"),
+        );
+
+        let cancellation = calendar.cancel_appointment(appointment.date_time).unwrap();
+        assert_eq!(cancellation.appointment, appointment);
+        assert_eq!(
+            cancellation.waitlisted,
+            vec![(AppointmentType::DentalCheckUp, jane)]
+        );
+        assert!(calendar.is_empty());
+
+        // Nothing left to cancel
+        assert_eq!(
+            calendar.cancel_appointment(appointment.date_time),
+            Err(CalendarError::NotFound)
+        );
+    }
+
+    // Test the total_working_slots and utilization functions
+    #[test]
+    fn test_utilization() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+        );
+
+        assert_eq!(calendar.total_working_slots(from, to), 32);
+        assert_eq!(calendar.utilization(from, to), 0.0);
+
+        // 8 of the 32 time slots
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        assert_eq!(calendar.utilization(from, to), 25.0);
+
+        // Weekend has no working time slots
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 3).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 4).unwrap(),
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+        );
+
+        assert_eq!(calendar.total_working_slots(from, to), 0);
+        assert_eq!(calendar.utilization(from, to), 0.0);
+    }
+
+    // Test the cancel_range function
+    #[test]
+    fn test_cancel_range() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(16, 45, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment.clone()).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(23, 59, 0).unwrap(),
+        );
+
+        let day_appointments = calendar.booked_appointments(Some(from), Some(to));
+        let cancelled_appointments = calendar.cancel_range(from, to);
+        assert_eq!(cancelled_appointments.len(), 2);
+        assert_eq!(cancelled_appointments, day_appointments);
+
+        assert!(calendar
+            .booked_appointments(Some(from), Some(to))
+            .is_empty());
+        assert_eq!(calendar.booked_appointments(None, None), vec![appointment]);
+    }
+
+    // Test the snap_to_working_slot function
+    #[test]
+    fn test_snap_to_working_slot() {
+        // Already a working time slot, so it doesn't advance
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        assert_eq!(date.snap_to_working_slot(), Some(date));
+        assert_ne!(date.get_next_working_datetime(None), date);
+
+        // Rounded up to the next 15 minute mark
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 7, 30).unwrap(),
+        );
+        assert_eq!(
+            date.snap_to_working_slot(),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 15, 0).unwrap()
+            ))
+        );
+
+        // During the break
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 10, 0).unwrap(),
+        );
+        assert_eq!(
+            date.snap_to_working_slot(),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+            ))
+        );
+
+        // Saturday snaps to Monday morning
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 3).unwrap(),
+            NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+        );
+        assert_eq!(
+            date.snap_to_working_slot(),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+            ))
+        );
+    }
+
+    // Test the all_by_duration_desc function
+    #[test]
+    fn test_all_by_duration_desc() {
+        let appointment_types = AppointmentType::all_by_duration_desc().collect::<Vec<_>>();
+        assert_eq!(
+            appointment_types,
+            vec![
+                AppointmentType::ImplantConsultation,
+                AppointmentType::DentalCheckUp,
+                AppointmentType::UrgentDentalAppointment,
+            ]
+        );
+    }
+
+    // Test the export_jsonl and import_jsonl functions
+    #[test]
+    fn test_export_import_jsonl() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        )
+        .with_patient(Patient::new("Jane Doe", "555-0100"));
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let mut output = vec![];
+        calendar.export_jsonl(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 2);
+
+        let mut imported_calendar = DoctorsCalendar::new();
+        let imported = imported_calendar
+            .import_jsonl(output.as_bytes(), RoundingPolicy::Reject)
+            .unwrap();
+        assert_eq!(imported, 2);
+        assert!(imported_calendar.appointments().eq(calendar.appointments()));
+
+        // Imported appointments are validated
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 3).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        let output = serde_json::to_string(&appointment).unwrap();
+        assert!(imported_calendar
+            .import_jsonl(output.as_bytes(), RoundingPolicy::Reject)
+            .is_err());
+
+        // An id already in use is rejected, whatever the date and time
+        let mut output = vec![];
+        calendar.export_jsonl(&mut output).unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(String::from_utf8(output).unwrap().lines().next().unwrap())
+                .unwrap();
+
+        let mut cancelled = line.clone();
+        cancelled["status"] = "Cancelled".into();
+        let mut moved = line.clone();
+        moved["date_time"] = "2024-02-01T10:00:00".into();
+
+        for duplicate in [cancelled, moved] {
+            let mut imported_calendar = DoctorsCalendar::new();
+            let result = imported_calendar.import_jsonl(
+                format!("{}\n{}\n", line, duplicate).as_bytes(),
+                RoundingPolicy::Reject,
+            );
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!("line 2: {}", CalendarError::DuplicateId)
+            );
+            assert_eq!(imported_calendar.len(), 1);
+        }
+
+        // Invalid JSON
+        let mut calendar = DoctorsCalendar::new();
+        assert!(calendar
+            .import_jsonl("not json\n".as_bytes(), RoundingPolicy::Reject)
+            .is_err());
+    }
+
+    // Test urgent appointments with an overridden duration
+    #[test]
+    fn test_new_with_duration() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Only urgent appointments can override the duration
+        assert_eq!(
+            DoctorsAppointment::new_with_duration(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                ),
+                AppointmentType::DentalCheckUp,
+                Duration::minutes(45),
+            ),
+            Err(CalendarError::InvalidDuration)
+        );
+
+        // The duration must be a multiple of 15 minutes
+        assert_eq!(
+            DoctorsAppointment::new_with_duration(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                ),
+                AppointmentType::UrgentDentalAppointment,
+                Duration::minutes(20),
+            ),
+            Err(CalendarError::InvalidDuration)
+        );
+
+        let appointment = DoctorsAppointment::new_with_duration(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+            Duration::minutes(30),
+        )
+        .unwrap();
+        assert_eq!(appointment.duration(), Duration::minutes(30));
+        assert_eq!(appointment.to_reserved_time_slots().len(), 2);
+        calendar.add_appointment(appointment).unwrap();
+
+        // Default urgent appointment overlapping the overridden one
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 15, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(appointment.duration(), Duration::minutes(15));
+        assert_eq!(
+            calendar.add_appointment(appointment),
+            Err(CalendarError::Overlap)
+        );
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        // Overridden appointment starting before an existing one and running into it
+        let appointment = DoctorsAppointment::new_with_duration(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+            Duration::minutes(45),
+        )
+        .unwrap();
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new_with_duration(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 45, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+            Duration::minutes(30),
+        )
+        .unwrap();
+        assert_eq!(
+            calendar.add_appointment(appointment),
+            Err(CalendarError::Overlap)
+        );
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        );
+
+        // 2 + 1 + 3 reserved time slots
+        let free_slots_urg = calendar.free_slots(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(free_slots_urg.len(), 10);
+    }
+
+    // Test the free_run_lengths function
+    #[test]
+    fn test_free_run_lengths() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        // The morning is split in two runs by the booking
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        );
+
+        assert_eq!(
+            calendar.free_run_lengths(from, to),
+            vec![
+                (from, 4),
+                (
+                    NaiveDateTime::new(
+                        NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                        NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                    ),
+                    10
+                ),
+            ]
+        );
+
+        // The afternoon run doesn't join the morning one across the break
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+        );
+
+        let runs = calendar.free_run_lengths(from, to);
+        assert_eq!(runs.len(), 3);
+        assert_eq!(
+            runs[2],
+            (
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+                ),
+                16
+            )
+        );
+    }
+
+    // Test parsing appointment types from aliases and display names
+    #[test]
+    fn test_appointment_type_from_str() {
+        assert_eq!(
+            "checkup".parse::<AppointmentType>(),
+            Ok(AppointmentType::DentalCheckUp)
+        );
+        assert_eq!(
+            "Check-Up".parse::<AppointmentType>(),
+            Ok(AppointmentType::DentalCheckUp)
+        );
+        assert_eq!(
+            "IMPLANT".parse::<AppointmentType>(),
+            Ok(AppointmentType::ImplantConsultation)
+        );
+        assert_eq!(
+            "urgent".parse::<AppointmentType>(),
+            Ok(AppointmentType::UrgentDentalAppointment)
+        );
+
+        // Display names round-trip
+        for appointment_type in AppointmentType::all_by_duration_desc() {
+            assert_eq!(
+                appointment_type.display_name().parse::<AppointmentType>(),
+                Ok(appointment_type)
+            );
+        }
+
+        assert_eq!(
+            "cleaning".parse::<AppointmentType>(),
+            Err(CalendarError::UnknownAppointmentType(
+                "cleaning".to_string()
+            ))
+        );
+    }
+
+    // Test the with_schedule function
+    #[test]
+    fn test_with_schedule_without_break() {
+        let mut calendar = DoctorsCalendar::with_schedule(
+            WorkingSchedule::continuous(
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            )
+            .unwrap(),
+        );
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        assert_eq!(calendar.available_single_time_slots(from, to).len(), 36);
+        assert_eq!(
+            calendar
+                .day_bitmap(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())
+                .len(),
+            36
+        );
+
+        // An appointment over the lunch time is allowed
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(11, 30, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        assert_eq!(calendar.add_appointment(appointment), Ok(()));
+    }
+
+    // Test the change_type function
+    #[test]
+    fn test_change_type() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let appointment = DoctorsAppointment::new(at, AppointmentType::DentalCheckUp)
+            .with_patient(Patient::new("Jane Doe", "555-0100"));
+        calendar.add_appointment(appointment).unwrap();
+
+        // A later booking collides with the longer implant consultation
+        let later_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(later_appointment.clone()).unwrap();
+
+        assert_eq!(
+            calendar.change_type(at, AppointmentType::ImplantConsultation),
+            Err(CalendarError::Overlap)
+        );
+
+        // The original appointment remains
+        let booked_appointments = calendar.booked_appointments(None, None);
+        assert_eq!(booked_appointments.len(), 2);
+        assert_eq!(
+            booked_appointments[0].appointment_type,
+            AppointmentType::DentalCheckUp
+        );
+
+        // Without the later booking, the upgrade fits
+        calendar
+            .cancel_appointment(later_appointment.date_time)
+            .unwrap();
+        assert_eq!(
+            calendar.change_type(at, AppointmentType::ImplantConsultation),
+            Ok(())
+        );
+
+        let booked_appointments = calendar.booked_appointments(None, None);
+        assert_eq!(booked_appointments.len(), 1);
+        assert_eq!(
+            booked_appointments[0].appointment_type,
+            AppointmentType::ImplantConsultation
+        );
+        assert_eq!(
+            booked_appointments[0].patient,
+            Some(Patient::new("Jane Doe", "555-0100"))
+        );
+
+        // An upgrade running into the break is rejected too
+        let at = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(11, 30, 0).unwrap(),
+        );
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at,
+                AppointmentType::UrgentDentalAppointment,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.change_type(at, AppointmentType::DentalCheckUp),
+            Ok(())
+        );
+        assert_eq!(
+            calendar.change_type(at, AppointmentType::ImplantConsultation),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+
+        assert_eq!(
+            calendar.change_type(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(15, 0, 0).unwrap(),
+                ),
+                AppointmentType::DentalCheckUp
+            ),
+            Err(CalendarError::NotFound)
+        );
+    }
+
+    // Test the free_slots_optimized_explained function
+    #[test]
+    fn test_free_slots_optimized_explained() {
+        let calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+
+        let explained = calendar.free_slots_optimized_explained(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+
+        // The explained slots are the optimized ones
+        assert_eq!(
+            explained
+                .iter()
+                .map(|optimized_slot| optimized_slot.slot)
+                .collect::<Vec<NaiveDateTime>>(),
+            calendar.free_slots_optimized(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment
+            )
+        );
+
+        // The 8:00 slot leaves room for two check-ups until 9:15
+        assert_eq!(explained[0].window_start, from);
+        assert_eq!(explained[0].slot, from);
+        assert_eq!(
+            explained[0].capacity,
+            BTreeMap::from([
+                (AppointmentType::DentalCheckUp, 2),
+                (AppointmentType::ImplantConsultation, 0),
+                (AppointmentType::UrgentDentalAppointment, 0),
+            ])
+        );
+    }
+
+    // Test the free_slots_optimized function with implant consultations running
+    // up to the break
+    #[test]
+    fn test_free_slots_optimized_implant_near_break() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 45, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+        );
+
+        // Only 10:00 to 10:30 fit before the break, and only 10:00 leaves room
+        // for a check-up after it
+        assert_eq!(
+            calendar.free_slots(Some(from), Some(to), AppointmentType::ImplantConsultation),
+            vec![
+                from,
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(10, 15, 0).unwrap()
+                ),
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(10, 30, 0).unwrap()
+                ),
+            ]
+        );
+        assert_eq!(
+            calendar.free_slots_optimized(
+                Some(from),
+                Some(to),
+                AppointmentType::ImplantConsultation
+            ),
+            vec![from]
+        );
+
+        // One slot per 60 minute window where an implant consultation fits: 8:00,
+        // 10:00, 13:00, 14:00 and 15:00
+        assert_eq!(
+            calendar
+                .free_slots_optimized(
+                    Some(
+                        from.date()
+                            .and_time(NaiveTime::from_hms_opt(8, 0, 0).unwrap())
+                    ),
+                    Some(
+                        from.date()
+                            .and_time(NaiveTime::from_hms_opt(17, 0, 0).unwrap())
+                    ),
+                    AppointmentType::ImplantConsultation
+                )
+                .len(),
+            5
+        );
+    }
+
+    // Test the add_appointment function with an appointment longer than every
+    // working hours window
+    #[test]
+    fn test_add_appointment_too_long_for_any_window() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new_with_duration(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+            Duration::hours(5),
+        )
+        .unwrap();
+
+        assert_eq!(
+            calendar.add_appointment(appointment.clone()),
+            Err(CalendarError::TooLongForAnyWindow(Duration::hours(5)))
+        );
+
+        // The same appointment fits in a schedule without a break
+        calendar.set_schedule(
+            WorkingSchedule::continuous(
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            )
+            .unwrap(),
+        );
+        assert_eq!(calendar.add_appointment(appointment), Ok(()));
+    }
+
+    // Test the diff function
+    #[test]
+    fn test_diff() {
+        let mut before = DoctorsCalendar::new();
+
+        let kept_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        let removed_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        before.add_appointment(kept_appointment).unwrap();
+        before.add_appointment(removed_appointment.clone()).unwrap();
+
+        assert!(before.diff(&before.clone()).is_empty());
+
+        let mut after = before.clone();
+        after
+            .cancel_appointment(removed_appointment.date_time)
+            .unwrap();
+
+        let added_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        after.add_appointment(added_appointment.clone()).unwrap();
+
+        assert_eq!(
+            before.diff(&after),
+            CalendarDiff {
+                added: vec![added_appointment.clone()],
+                removed: vec![removed_appointment.clone()],
+            }
+        );
+        assert_eq!(
+            after.diff(&before),
+            CalendarDiff {
+                added: vec![removed_appointment],
+                removed: vec![added_appointment],
+            }
+        );
+    }
+
+    // Test the free_slots_page function
+    #[test]
+    fn test_free_slots_page() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+
+        let free_slots = calendar.free_slots(Some(from), Some(to), AppointmentType::DentalCheckUp);
+
+        // Page 2 of size 5
+        assert_eq!(
+            calendar.free_slots_page(Some(from), Some(to), AppointmentType::DentalCheckUp, 5, 5),
+            free_slots[5..10].to_vec()
+        );
+
+        // The last page is cut short
+        assert_eq!(
+            calendar.free_slots_page(
+                Some(from),
+                Some(to),
+                AppointmentType::DentalCheckUp,
+                free_slots.len() - 2,
+                5
+            ),
+            free_slots[free_slots.len() - 2..].to_vec()
+        );
+
+        // Past the last page
+        assert!(calendar
+            .free_slots_page(
+                Some(from),
+                Some(to),
+                AppointmentType::DentalCheckUp,
+                free_slots.len(),
+                5
+            )
+            .is_empty());
+    }
+
+    // Test the color function
+    #[test]
+    fn test_color() {
+        assert_eq!(AppointmentType::DentalCheckUp.color(), "#2e7d32");
+        assert_eq!(AppointmentType::ImplantConsultation.color(), "#1565c0");
+        assert_eq!(AppointmentType::UrgentDentalAppointment.color(), "#c62828");
+    }
+
+    // Test the hold, confirm and release functions
+    #[test]
+    fn test_holds() {
+        let now = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+        );
+        let mut calendar = DoctorsCalendar::with_clock(Arc::new(FixedClock(now)));
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+
+        let appointment = DoctorsAppointment::new(from, AppointmentType::DentalCheckUp);
+        let hold_id = calendar
+            .hold(appointment.clone(), now + Duration::minutes(15))
+            .unwrap();
+
+        // The held time slot is not free, and can't be booked
+        assert!(!calendar
+            .free_slots(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment
+            )
+            .contains(&from));
+        assert_eq!(
+            calendar.add_appointment(appointment.clone()),
+            Err(CalendarError::Held)
+        );
+        assert_eq!(calendar.holds().count(), 1);
+
+        // Released holds free the time slot again
+        assert!(calendar.release(hold_id));
+        assert!(!calendar.release(hold_id));
+        assert!(calendar
+            .free_slots(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment
+            )
+            .contains(&from));
+        assert_eq!(calendar.confirm(hold_id), Err(CalendarError::HoldNotFound));
+
+        // Confirmed holds are booked
+        let hold_id = calendar
+            .hold(appointment.clone(), now + Duration::minutes(15))
+            .unwrap();
+        assert_eq!(calendar.confirm(hold_id), Ok(()));
+        assert_eq!(calendar.booked_appointments(None, None), vec![appointment]);
+        assert_eq!(calendar.holds().count(), 0);
+
+        // Expired holds don't reserve the time slot
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        let hold_id = calendar
+            .hold(appointment.clone(), now - Duration::minutes(1))
+            .unwrap();
+        assert!(calendar
+            .free_slots(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment
+            )
+            .contains(&appointment.date_time));
+        assert_eq!(calendar.confirm(hold_id), Err(CalendarError::HoldNotFound));
+        assert_eq!(calendar.add_appointment(appointment), Ok(()));
+    }
+
+    // Test the next_available and next_available_slots functions
+    #[test]
+    fn test_next_available() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+        );
+        let appointment = DoctorsAppointment::new(at, AppointmentType::DentalCheckUp);
+        calendar.add_appointment(appointment).unwrap();
+
+        assert_eq!(
+            calendar.next_available(at, AppointmentType::DentalCheckUp),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(11, 30, 0).unwrap()
+            ))
+        );
+
+        // The suggestions continue after the break
+        assert_eq!(
+            calendar.next_available_slots(at, AppointmentType::DentalCheckUp, 3),
+            vec![
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(11, 30, 0).unwrap()
+                ),
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+                ),
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(13, 15, 0).unwrap()
+                ),
+            ]
+        );
+
+        // Friday evening continues on Monday
+        assert_eq!(
+            calendar.next_available(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                    NaiveTime::from_hms_opt(16, 45, 0).unwrap(),
+                ),
+                AppointmentType::ImplantConsultation
+            ),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+            ))
+        );
+    }
+
+    // Test the snapshot function
+    #[test]
+    fn test_snapshot() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+
+        let appointment = DoctorsAppointment::new(from, AppointmentType::DentalCheckUp);
+        calendar.add_appointment(appointment.clone()).unwrap();
+
+        let snapshot = calendar.snapshot();
+
+        // Later changes to the calendar are not visible in the snapshot
+        calendar.cancel_appointment(from).unwrap();
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                to,
+                AppointmentType::UrgentDentalAppointment,
+            ))
+            .unwrap();
+
+        assert_eq!(snapshot.booked_appointments(None, None), vec![appointment]);
+        assert_eq!(
+            snapshot.free_slots(Some(from), Some(to), AppointmentType::DentalCheckUp),
+            vec![
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+                ),
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(8, 45, 0).unwrap()
+                ),
+                to,
+            ]
+        );
+
+        // The snapshot can be queried from another thread
+        let snapshot = Arc::new(snapshot);
+        let handle = {
+            let snapshot = Arc::clone(&snapshot);
+            std::thread::spawn(move || snapshot.booked_appointments(None, None).len())
+        };
+        assert_eq!(handle.join().unwrap(), 1);
+    }
+
+    // Test the neighbors function
+    #[test]
+    fn test_neighbors() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let first_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        let second_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(first_appointment.clone()).unwrap();
+        calendar
+            .add_appointment(second_appointment.clone())
+            .unwrap();
+
+        // Between the two bookings
+        assert_eq!(
+            calendar.neighbors(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+            )),
+            (
+                Some(first_appointment.clone()),
+                Some(second_appointment.clone())
+            )
+        );
+
+        // At the start of the day
+        assert_eq!(
+            calendar.neighbors(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            )),
+            (None, Some(first_appointment.clone()))
+        );
+
+        // At the start of a booking
+        assert_eq!(
+            calendar.neighbors(second_appointment.date_time),
+            (Some(first_appointment), Some(second_appointment.clone()))
+        );
+
+        // At the end of the day
+        assert_eq!(
+            calendar.neighbors(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            )),
+            (Some(second_appointment), None)
+        );
+    }
+
+    // Test the minimum notice per appointment type
+    #[test]
+    fn test_min_lead() {
+        let now = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+        let mut calendar = DoctorsCalendar::with_clock(Arc::new(FixedClock(now)));
+        calendar.set_catalog(
+            AppointmentCatalog::new()
+                .with_min_lead(AppointmentType::ImplantConsultation, Duration::hours(24)),
+        );
+
+        let soon = now + Duration::minutes(30);
+
+        // An urgent slot 30 minutes out is offered, but not an implant one
+        assert!(calendar
+            .free_slots(None, None, AppointmentType::UrgentDentalAppointment)
+            .contains(&soon));
+        assert!(!calendar
+            .free_slots(None, None, AppointmentType::ImplantConsultation)
+            .contains(&soon));
+        assert_eq!(
+            calendar.free_slots(None, None, AppointmentType::ImplantConsultation)[0],
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+            )
+        );
+
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                soon,
+                AppointmentType::ImplantConsultation
+            )),
+            Err(CalendarError::InsufficientNotice)
+        );
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                soon,
+                AppointmentType::UrgentDentalAppointment
+            )),
+            Ok(())
+        );
+    }
+
+    // Test the iter and into_iter functions
+    #[test]
+    fn test_iter() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let later_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        let earlier_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(later_appointment.clone()).unwrap();
+        calendar
+            .add_appointment(earlier_appointment.clone())
+            .unwrap();
+
+        assert_eq!(
+            calendar.iter().collect::<Vec<&DoctorsAppointment>>(),
+            vec![&earlier_appointment, &later_appointment]
+        );
+
+        let mut date_times = vec![];
+        for appointment in &calendar {
+            date_times.push(appointment.date_time);
+        }
+        assert_eq!(
+            date_times,
+            vec![earlier_appointment.date_time, later_appointment.date_time]
+        );
+
+        assert_eq!(
+            calendar.into_iter().collect::<Vec<DoctorsAppointment>>(),
+            vec![earlier_appointment, later_appointment]
+        );
+    }
+
+    // Test the auto_book function
+    #[test]
+    fn test_auto_book() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Leave 8:00 as a single free time slot before a longer free run
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 15, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        );
+
+        // First fit takes the earliest free time slot
+        let mut first_fit_calendar = calendar.clone();
+        assert_eq!(
+            first_fit_calendar.auto_book(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment,
+                PlacementStrategy::FirstFit
+            ),
+            Ok(from)
+        );
+
+        // Best fit follows the optimizer, which prefers the time slot with the
+        // most room for implant consultations connected to it
+        let booked = calendar.auto_book(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+            PlacementStrategy::BestFit,
+        );
+        assert_eq!(
+            booked,
+            Ok(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+            ))
+        );
+        assert_eq!(calendar.len(), 2);
+
+        // No implant consultation fits starting by 8:15
+        assert_eq!(
+            calendar.auto_book(
+                Some(from),
+                Some(from + Duration::minutes(15)),
+                AppointmentType::ImplantConsultation,
+                PlacementStrategy::FirstFit
+            ),
+            Err(CalendarError::NoFreeSlot)
+        );
+    }
+
+    // Test the weekly_utilization function
+    #[test]
+    fn test_weekly_utilization() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Friday the 1st of March is in the same ISO week as February
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let weeks = calendar.weekly_utilization(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 26).unwrap(),
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+            ),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 3, 9).unwrap(),
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+            ),
+        );
+
+        assert_eq!(
+            weeks,
+            BTreeMap::from([((2024, 9), (2, 160)), ((2024, 10), (1, 160))])
+        );
+    }
+
+    // Test the free_slots_for function
+    #[test]
+    fn test_free_slots_for() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        calendar.add_appointment(appointment.clone()).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+
+        let busy = [(appointment.date_time, appointment.end_time())];
+
+        // The calendar lets the appointments end after the `to` time
+        assert_eq!(
+            free_slots_for(
+                &busy,
+                from,
+                to + AppointmentType::DentalCheckUp.duration(),
+                AppointmentType::DentalCheckUp,
+                &WorkingSchedule::default()
+            ),
+            calendar.free_slots(Some(from), Some(to), AppointmentType::DentalCheckUp)
+        );
+
+        // A busy interval covering part of a time slot reserves it
+        let busy = [(from + Duration::minutes(10), from + Duration::minutes(20))];
+        assert_eq!(
+            free_slots_for(
+                &busy,
+                from,
+                from + Duration::minutes(60),
+                AppointmentType::UrgentDentalAppointment,
+                &WorkingSchedule::default()
+            ),
+            vec![from + Duration::minutes(30), from + Duration::minutes(45)]
+        );
+    }
+
+    // Test the free_slots_optimized_with_priority function
+    #[test]
+    fn test_free_slots_optimized_with_priority() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Leave 7 free time slots from 8:00 to 9:45
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 45, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+        );
+
+        // By default, 8:00 leaves room for an implant consultation after it
+        let default_priority: Vec<AppointmentType> =
+            AppointmentType::all_by_duration_desc().collect();
+        assert_eq!(
+            calendar.free_slots_optimized_with_priority(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment,
+                &default_priority
+            ),
+            calendar.free_slots_optimized(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment
+            )
+        );
+        assert_eq!(
+            calendar.free_slots_optimized(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment
+            )[0],
+            from
+        );
+
+        // Prioritizing urgent appointments, 8:15 leaves room for two urgent
+        // appointments and two check-ups around it
+        assert_eq!(
+            calendar.free_slots_optimized_with_priority(
+                Some(from),
+                Some(to),
+                AppointmentType::UrgentDentalAppointment,
+                &[
+                    AppointmentType::UrgentDentalAppointment,
+                    AppointmentType::DentalCheckUp,
+                    AppointmentType::ImplantConsultation,
+                ]
+            )[0],
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 15, 0).unwrap()
+            )
+        );
+    }
+
+    // Test the filter_by_tag function
+    #[test]
+    fn test_filter_by_tag() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let tagged_appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        )
+        .with_tag("new-patient")
+        .with_tag("insurance-pending");
+        calendar
+            .add_appointment(tagged_appointment.clone())
+            .unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        )
+        .with_tag("follow-up");
+        calendar.add_appointment(appointment).unwrap();
+
+        assert_eq!(
+            calendar.filter_by_tag("new-patient", None, None),
+            vec![tagged_appointment.clone()]
+        );
+        assert!(calendar
+            .filter_by_tag("new-patient", Some(tagged_appointment.end_time()), None)
+            .is_empty());
+        assert!(calendar.filter_by_tag("unknown", None, None).is_empty());
+
+        // Tags survive changing the appointment type
+        calendar
+            .change_type(
+                tagged_appointment.date_time,
+                AppointmentType::UrgentDentalAppointment,
+            )
+            .unwrap();
+        assert_eq!(
+            calendar.filter_by_tag("insurance-pending", None, None)[0].appointment_type,
+            AppointmentType::UrgentDentalAppointment
+        );
+
+        // Tags are serialized
+        let mut output = vec![];
+        calendar.export_jsonl(&mut output).unwrap();
+        let mut imported_calendar = DoctorsCalendar::new();
+        imported_calendar
+            .import_jsonl(output.as_slice(), RoundingPolicy::Reject)
+            .unwrap();
+        assert_eq!(
+            imported_calendar
+                .filter_by_tag("follow-up", None, None)
+                .len(),
+            1
+        );
+    }
+
+    // Test the booked_slot_count function
+    #[test]
+    fn test_booked_slot_count() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        assert_eq!(calendar.booked_appointments(Some(from), Some(to)).len(), 2);
+        assert_eq!(calendar.booked_slot_count(from, to), 7);
+    }
+
+    // Test the fill_random function with a percentage above 100
+    #[test]
+    fn test_fill_random_above_100_percent() {
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        );
+
+        // A double booking counts its time slot twice, so 100% of the 16 time
+        // slots is reached with one of them still free
+        let mut calendar = DoctorsCalendar::new();
+        let urgent = DoctorsAppointment::new(from, AppointmentType::UrgentDentalAppointment);
+        calendar.add_appointment(urgent.clone()).unwrap();
+        calendar.force_book(urgent).unwrap();
+
+        // Treated as 100%, it stops there instead of filling every free slot
+        calendar.fill_random(from, to, AppointmentType::UrgentDentalAppointment, 150);
+        assert_eq!(calendar.len(), 16);
+        assert_eq!(calendar.booked_slot_count(from, to), 16);
+        assert_eq!(
+            calendar
+                .free_slots(
+                    Some(from),
+                    Some(to),
+                    AppointmentType::UrgentDentalAppointment
+                )
+                .len(),
+            1
+        );
+    }
+
+    // Test the reserved_slots function
+    #[test]
+    fn test_reserved_slots() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment).unwrap();
+
+        calendar.add_block(Block::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            ),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            ),
+            "Admin",
+        ));
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        let reserved_slots = calendar.reserved_slots(from, to);
+        let available_slots = calendar.available_single_time_slots(from, to);
+
+        assert_eq!(reserved_slots.len(), 6);
+        assert!(reserved_slots.contains(&NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 15, 0).unwrap()
+        )));
+
+        // Reserved and available time slots partition the working time slots
+        assert!(available_slots
+            .iter()
+            .all(|time_slot| !reserved_slots.contains(time_slot)));
+        assert_eq!(
+            reserved_slots.len() + available_slots.len(),
+            calendar.total_working_slots(from, to)
+        );
+    }
+
+    // Test the audit_log function
+    #[test]
+    fn test_audit_log() {
+        let now = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap(),
+            NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+        );
+        let mut calendar = DoctorsCalendar::with_clock(Arc::new(FixedClock(now))).with_audit_log();
+
+        let appointment = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(appointment.clone()).unwrap();
+
+        // Failed changes are not recorded
+        assert!(calendar.add_appointment(appointment.clone()).is_err());
+
+        calendar.cancel_appointment(appointment.date_time).unwrap();
+
+        assert_eq!(
+            calendar.audit_log(),
+            &[
+                AuditEntry {
+                    at: now,
+                    operation: AuditOperation::Add,
+                    appointment: appointment.clone(),
+                },
+                AuditEntry {
+                    at: now,
+                    operation: AuditOperation::Cancel,
+                    appointment: appointment.clone(),
+                },
+            ]
+        );
+
+        // Every moved appointment is recorded, as are status changes
+        calendar.add_appointment(appointment.clone()).unwrap();
+        let later_appointment = DoctorsAppointment::new(
+            appointment.date_time + Duration::hours(1),
+            AppointmentType::DentalCheckUp,
+        );
+        calendar.add_appointment(later_appointment.clone()).unwrap();
+        let entries = calendar.audit_log().len();
+
+        calendar
+            .shift_range(
+                appointment.date_time,
+                later_appointment.end_time(),
+                Duration::minutes(30),
+            )
+            .unwrap();
+        calendar
+            .set_status(
+                appointment.date_time + Duration::minutes(30),
+                AppointmentStatus::Completed,
+            )
+            .unwrap();
+
+        let moved = |appointment: &DoctorsAppointment| {
+            let mut moved_appointment = appointment.clone();
+            moved_appointment.date_time += Duration::minutes(30);
+            moved_appointment
+        };
+        let mut completed_appointment = moved(&appointment);
+        completed_appointment.status = AppointmentStatus::Completed;
+        assert_eq!(
+            &calendar.audit_log()[entries..],
+            &[
+                AuditEntry {
+                    at: now,
+                    operation: AuditOperation::Reschedule,
+                    appointment: moved(&appointment),
+                },
+                AuditEntry {
+                    at: now,
+                    operation: AuditOperation::Reschedule,
+                    appointment: moved(&later_appointment),
+                },
+                AuditEntry {
+                    at: now,
+                    operation: AuditOperation::StatusChange,
+                    appointment: completed_appointment,
+                },
+            ]
+        );
+
+        // A shift that doesn't fit is not recorded
+        let entries = calendar.audit_log().len();
+        assert!(calendar
+            .shift_range(
+                appointment.date_time,
+                later_appointment.end_time() + Duration::hours(1),
+                Duration::hours(12),
+            )
+            .is_err());
+        assert_eq!(calendar.audit_log().len(), entries);
+
+        // The audit log is disabled by default
+        let mut calendar = DoctorsCalendar::new();
+        calendar.add_appointment(appointment).unwrap();
+        assert!(calendar.audit_log().is_empty());
+    }
+
+    // Test the set_optimizer_window function
+    #[test]
+    fn test_optimizer_window() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(16, 45, 0).unwrap(),
+        );
+
+        let hourly_slots = calendar.free_slots_optimized(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(hourly_slots.len(), 8);
+
+        // Twice as many windows of 30 minutes
+        calendar
+            .set_optimizer_window(Duration::minutes(30))
+            .unwrap();
+        let half_hourly_slots = calendar.free_slots_optimized(
+            Some(from),
+            Some(to),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        assert_eq!(half_hourly_slots.len(), 16);
+        assert_eq!(
+            half_hourly_slots[1],
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+            )
+        );
+
+        // Windows of 120 minutes from midnight: 8:00, 10:00, 12:00 (free from 13:00),
+        // 14:00 and 16:00
+        calendar
+            .set_optimizer_window(Duration::minutes(120))
+            .unwrap();
+        assert_eq!(
+            calendar
+                .free_slots_optimized(
+                    Some(from),
+                    Some(to),
+                    AppointmentType::UrgentDentalAppointment
+                )
+                .len(),
+            5
+        );
+
+        assert_eq!(
+            calendar.set_optimizer_window(Duration::minutes(20)),
+            Err(CalendarError::InvalidDuration)
+        );
+        assert_eq!(calendar.optimizer_window(), Duration::minutes(120));
+    }
+
+    // Test the is_slot_free function
+    #[test]
+    fn test_is_slot_free() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let date_time = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                date_time,
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+
+        // The check-up reserves 9:00 to 9:30
+        assert!(!calendar.is_slot_free(date_time));
+        assert!(!calendar.is_slot_free(date_time + Duration::minutes(15)));
+        assert!(calendar.is_slot_free(date_time + Duration::minutes(30)));
+        assert!(calendar.is_slot_free(date_time - Duration::minutes(15)));
+
+        // Not on a 15 minute mark
+        assert!(!calendar.is_slot_free(date_time + Duration::minutes(35)));
+
+        // Lunch break
+        assert!(!calendar.is_slot_free(NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 15, 0).unwrap(),
+        )));
+    }
+
+    // Test the free_slots_report_md function
+    #[test]
+    fn test_free_slots_report_md() {
+        let mut calendar = DoctorsCalendar::new();
+
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(15, 0, 0).unwrap(),
+                ),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+        );
+
+        let report = calendar.free_slots_report_md(Some(from), Some(to));
+
+        assert_eq!(
+            report,
+            "## Thursday 2024-02-01\n\n\
+             | Type | Free time slots |\n\
+             | --- | --- |\n\
+             | Implant Consultation | - |\n\
+             | Check-up | 16:30 |\n\
+             | Urgent Appointment | 16:30, 16:45 |\n\
+             \n\
+             ## Friday 2024-02-02\n\n\
+             | Type | Free time slots |\n\
+             | --- | --- |\n\
+             | Implant Consultation | 08:00, 08:15, 08:30 |\n\
+             | Check-up | 08:00, 08:15, 08:30 |\n\
+             | Urgent Appointment | 08:00, 08:15, 08:30 |\n\
+             \n"
+        );
+    }
+
+    // Test the add_appointment_snapped function
+    #[test]
+    fn test_add_appointment_snapped() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let requested = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 7, 42).unwrap(),
+        );
+
+        // Snaps down to 8:00 when it's free
+        assert_eq!(
+            calendar.add_appointment_snapped(DoctorsAppointment::new(
+                requested,
+                AppointmentType::DentalCheckUp
+            )),
+            Ok(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ))
+        );
+
+        // Otherwise to the next free time slot, after the check-up
+        assert_eq!(
+            calendar.add_appointment_snapped(DoctorsAppointment::new(
+                requested,
+                AppointmentType::DentalCheckUp
+            )),
+            Ok(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+            ))
+        );
+        assert_eq!(calendar.len(), 2);
+
+        // Before the working hours, snaps to the start of the day
+        assert_eq!(
+            calendar.add_appointment_snapped(DoctorsAppointment::new(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                    NaiveTime::from_hms_opt(6, 59, 59).unwrap(),
+                ),
+                AppointmentType::UrgentDentalAppointment
+            )),
+            Ok(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ))
+        );
+    }
+
+    // Test the busy_intervals function
+    #[test]
+    fn test_busy_intervals() {
+        let mut calendar = DoctorsCalendar::new();
+
+        for (hour, minute) in [(8, 0), (8, 30), (10, 0)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    NaiveDateTime::new(
+                        NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                        NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+                    ),
+                    AppointmentType::DentalCheckUp,
+                ))
+                .unwrap();
+        }
+
+        // The back-to-back check-ups are merged, the later one is separate
+        assert_eq!(
+            calendar.busy_intervals(at(0, 0), at(23, 0)),
+            vec![(at(8, 0), at(9, 0)), (at(10, 0), at(10, 30))]
+        );
+
+        // The intervals are clipped to the time period
+        assert_eq!(
+            calendar.busy_intervals(at(8, 15), at(10, 15)),
+            vec![(at(8, 15), at(9, 0)), (at(10, 0), at(10, 15))]
+        );
+
+        assert!(calendar.busy_intervals(at(9, 0), at(10, 0)).is_empty());
+    }
+
+    // Test the set_status function
+    #[test]
+    fn test_set_status() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let date_time = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                date_time,
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert!(!calendar
+            .free_slots(Some(from), Some(to), AppointmentType::DentalCheckUp)
+            .contains(&date_time));
+
+        // Cancelling the appointment frees its time slots, but keeps it
+        calendar
+            .set_status(date_time, AppointmentStatus::Cancelled)
+            .unwrap();
+        assert!(calendar
+            .free_slots(Some(from), Some(to), AppointmentType::DentalCheckUp)
+            .contains(&date_time));
+        assert_eq!(calendar.len(), 1);
+
+        // The freed time slots can be booked again
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                date_time + Duration::minutes(15),
+                AppointmentType::UrgentDentalAppointment,
+            ))
+            .unwrap();
+
+        // The cancelled appointment can't be restored over the new one
+        assert_eq!(
+            calendar.set_status(date_time, AppointmentStatus::Scheduled),
+            Err(CalendarError::Overlap)
+        );
+        assert_eq!(
+            calendar.set_status(
+                date_time + Duration::minutes(15),
+                AppointmentStatus::Completed
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            calendar.set_status(date_time + Duration::hours(1), AppointmentStatus::NoShow),
+            Err(CalendarError::NotFound)
+        );
+    }
+
+    // Test the no_show_rate function
+    #[test]
+    fn test_no_show_rate() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let to = from + Duration::days(1);
+
+        assert_eq!(calendar.no_show_rate(from, to), 0.0);
+
+        for (hour, status) in [
+            (8, AppointmentStatus::Completed),
+            (9, AppointmentStatus::NoShow),
+            (10, AppointmentStatus::Completed),
+            (11, AppointmentStatus::Completed),
+            (13, AppointmentStatus::Scheduled),
+        ] {
+            let date_time = NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            );
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    date_time,
+                    AppointmentType::DentalCheckUp,
+                ))
+                .unwrap();
+            calendar.set_status(date_time, status).unwrap();
+        }
+
+        // One no-show out of four past appointments, the scheduled one doesn't count
+        assert_eq!(calendar.no_show_rate(from, to), 25.0);
+
+        // Archived no-shows count too, like in no_show_count
+        calendar
+            .cancel_appointment_with_reason(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+                ),
+                CancellationReason::NoShow,
+            )
+            .unwrap();
+        assert_eq!(calendar.no_show_count(Some(from), Some(to)), 2);
+        assert_eq!(calendar.no_show_rate(from, to), 40.0);
+    }
+
+    // Test the least_busy_day function
+    #[test]
+    fn test_least_busy_day() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Monday is nearly full, Wednesday has a single appointment
+        calendar.fill_random(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            ),
+            AppointmentType::UrgentDentalAppointment,
+            90,
+        );
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 7).unwrap(),
+                    NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                ),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+
+        // Tuesday is the first empty day
+        assert_eq!(
+            calendar.least_busy_day(
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 2, 11).unwrap()
+            ),
+            Some(NaiveDate::from_ymd_opt(2024, 2, 6).unwrap())
+        );
+        assert_eq!(
+            calendar.least_busy_day(
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 2, 5).unwrap()
+            ),
+            Some(NaiveDate::from_ymd_opt(2024, 2, 5).unwrap())
+        );
+
+        // Only a weekend
+        assert_eq!(
+            calendar.least_busy_day(
+                NaiveDate::from_ymd_opt(2024, 2, 10).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 2, 11).unwrap()
+            ),
+            None
+        );
+    }
+
+    // Test the import_jsonl function with a rounding policy
+    #[test]
+    fn test_import_jsonl_rounding_policy() {
+        let line = r#"{"date_time":"2024-02-01T08:00:00","appointment_type":"UrgentDentalAppointment","duration_minutes":20}"#;
+
+        // Rejected by default
+        let mut calendar = DoctorsCalendar::new();
+        assert!(calendar
+            .import_jsonl(line.as_bytes(), RoundingPolicy::Reject)
+            .is_err());
+
+        // Rounded up to 30 minutes
+        assert_eq!(
+            calendar
+                .import_jsonl(line.as_bytes(), RoundingPolicy::RoundUp)
+                .unwrap(),
+            1
+        );
+        let appointment = calendar.appointments().next().unwrap();
+        assert_eq!(appointment.duration(), Duration::minutes(30));
+        assert_eq!(appointment.to_reserved_time_slots().len(), 2);
+
+        // Rounded to 15 minutes
+        let mut calendar = DoctorsCalendar::new();
+        calendar
+            .import_jsonl(line.as_bytes(), RoundingPolicy::RoundNearest)
+            .unwrap();
+        assert_eq!(
+            calendar.appointments().next().unwrap().duration(),
+            Duration::minutes(15)
+        );
+
+        // The rounded duration is validated against the working hours
+        let line = r#"{"date_time":"2024-02-01T11:45:00","appointment_type":"UrgentDentalAppointment","duration_minutes":20}"#;
+        assert!(calendar
+            .import_jsonl(line.as_bytes(), RoundingPolicy::RoundUp)
+            .is_err());
+        assert_eq!(
+            calendar
+                .import_jsonl(line.as_bytes(), RoundingPolicy::RoundNearest)
+                .unwrap(),
+            1
+        );
+    }
+
+    // Test the apply function of the rounding policy
+    #[test]
+    fn test_rounding_policy_apply() {
+        assert_eq!(
+            RoundingPolicy::RoundUp.apply(Duration::minutes(31)),
+            Duration::minutes(45)
+        );
+        assert_eq!(
+            RoundingPolicy::RoundUp.apply(Duration::minutes(45)),
+            Duration::minutes(45)
+        );
+        assert_eq!(
+            RoundingPolicy::RoundNearest.apply(Duration::minutes(37)),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            RoundingPolicy::RoundNearest.apply(Duration::minutes(38)),
+            Duration::minutes(45)
+        );
+        assert_eq!(
+            RoundingPolicy::Reject.apply(Duration::minutes(40)),
+            Duration::minutes(40)
+        );
+    }
+
+    // Test the try_new function
+    #[test]
+    fn test_try_new() {
+        let date_time = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 7, 0).unwrap(),
+        );
+
+        // Unaligned starts are only rejected by `try_new`
+        assert_eq!(
+            DoctorsAppointment::try_new(date_time, AppointmentType::DentalCheckUp),
+            Err(CalendarError::UnalignedStart(date_time))
+        );
+        assert_eq!(
+            DoctorsAppointment::new(date_time, AppointmentType::DentalCheckUp).date_time,
+            date_time
+        );
+
+        let date_time = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 15, 0).unwrap(),
+        );
+        assert_eq!(
+            DoctorsAppointment::try_from((date_time, AppointmentType::DentalCheckUp)),
+            Ok(DoctorsAppointment::new(
+                date_time,
+                AppointmentType::DentalCheckUp
+            ))
+        );
+
+        // Running past midnight
+        let date_time = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
+        );
+        assert_eq!(
+            DoctorsAppointment::try_new(date_time, AppointmentType::ImplantConsultation),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+        assert!(DoctorsAppointment::try_new(date_time, AppointmentType::DentalCheckUp).is_ok());
+    }
+
+    // Test the free_slots function with Sunday to Thursday working days
+    #[test]
+    fn test_free_slots_sunday_to_thursday() {
+        let calendar =
+            DoctorsCalendar::with_schedule(WorkingSchedule::default().with_working_days(&[
+                Weekday::Sun,
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+            ]));
+
+        let free_slots_on = |date: NaiveDate| {
+            calendar.free_slots(
+                Some(date.and_time(NaiveTime::MIN)),
+                Some(date.and_hms_opt(23, 45, 0).unwrap()),
+                AppointmentType::DentalCheckUp,
+            )
+        };
+
+        // Friday and Saturday are the weekend
+        assert!(free_slots_on(NaiveDate::from_ymd_opt(2024, 2, 2).unwrap()).is_empty());
+        assert!(free_slots_on(NaiveDate::from_ymd_opt(2024, 2, 3).unwrap()).is_empty());
+
+        let sunday_slots = free_slots_on(NaiveDate::from_ymd_opt(2024, 2, 4).unwrap());
+        assert_eq!(sunday_slots.len(), 30);
+        assert_eq!(
+            sunday_slots[0],
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 4).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+            )
+        );
+    }
+
+    // Test the availability_heatmap function
+    #[test]
+    fn test_availability_heatmap() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Thursday 8:00 on the first week only
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                ),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+
+        // Two full weeks, from Monday to Monday
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 1, 29).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let heatmap = calendar.availability_heatmap(from, from + Duration::weeks(2));
+
+        // Monday to Friday, 32 working time slots a day
+        assert_eq!(heatmap.len(), 5);
+        assert!(heatmap.iter().all(|row| row.len() == 32));
+
+        assert_eq!(heatmap[0], vec![2; 32]);
+        assert_eq!(heatmap[3][0], 1);
+        assert_eq!(heatmap[3][1], 1);
+        assert_eq!(heatmap[3][2], 2);
+
+        // One-off windows on the first day don't change the columns
+        let mut extended_calendar = calendar.clone();
+        extended_calendar
+            .extend_day(
+                from.date(),
+                (
+                    NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+                    NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                ),
+            )
+            .unwrap();
+        assert_eq!(
+            extended_calendar.availability_heatmap(from, from + Duration::weeks(2)),
+            heatmap
+        );
+
+        // Neither does starting on an extended non-working day
+        let saturday = from - Duration::days(2);
+        let mut extended_calendar = calendar.clone();
+        extended_calendar
+            .extend_day(
+                saturday.date(),
+                (
+                    NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                    NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+                ),
+            )
+            .unwrap();
+        assert_eq!(
+            extended_calendar.availability_heatmap(saturday, from + Duration::weeks(2)),
+            heatmap
+        );
+    }
+
+    // Test the add_composite function
+    #[test]
+    fn test_add_composite() {
+        let mut calendar = DoctorsCalendar::new().with_audit_log();
+
+        let prep = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let main = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+        );
+
+        let ids = calendar
+            .add_composite(&[
+                (prep, AppointmentType::UrgentDentalAppointment),
+                (main, AppointmentType::ImplantConsultation),
+            ])
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(calendar.appointment(ids[0]).unwrap().date_time, prep);
+        assert_eq!(calendar.appointment(ids[1]).unwrap().date_time, main);
+
+        // The second part overlaps with the implant consultation, so neither is
+        // booked
+        let before = calendar.clone();
+        assert_eq!(
+            calendar.add_composite(&[
+                (
+                    prep + Duration::days(1),
+                    AppointmentType::UrgentDentalAppointment
+                ),
+                (main + Duration::minutes(30), AppointmentType::DentalCheckUp),
+            ]),
+            Err(CalendarError::Overlap)
+        );
+        assert!(calendar.appointments().eq(before.appointments()));
+        assert_eq!(calendar.audit_log().len(), 2);
+        assert!(calendar.diff(&before).is_empty());
+    }
+
+    // Test the overlap check of the add_appointment function with many
+    // appointments, against checking every appointment
+    #[test]
+    fn test_add_appointment_overlap_many() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = from + Duration::weeks(2);
+
+        // Long urgent appointments, so overlapping appointments can start well
+        // before the checked time
+        for days in [0, 3, 8] {
+            calendar
+                .add_appointment(
+                    DoctorsAppointment::new_with_duration(
+                        from + Duration::days(days),
+                        AppointmentType::UrgentDentalAppointment,
+                        Duration::hours(3),
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+        }
+        calendar.fill_random(from, to, AppointmentType::DentalCheckUp, 40);
+        calendar.fill_random(from, to, AppointmentType::ImplantConsultation, 60);
+        assert!(calendar.len() > 50);
+
+        let mut current = from;
+        while current < to {
+            for appointment_type in AppointmentType::all_by_duration_desc() {
+                let appointment = DoctorsAppointment::new(current, appointment_type);
+
+                // Only check the appointments within the working hours
+                if calendar
+                    .schedule()
+                    .end_time_respecting_breaks(current, appointment.duration())
+                    .is_err()
+                {
+                    continue;
+                }
+
+                let overlaps = calendar
+                    .appointments()
+                    .any(|existing_appointment| existing_appointment.overlaps(&appointment));
+
+                let expected = if overlaps {
+                    Err(CalendarError::Overlap)
+                } else {
+                    Ok(())
+                };
+                assert_eq!(calendar.clone().add_appointment(appointment), expected);
+            }
+
+            current += Duration::minutes(15);
+        }
+    }
+
+    // Test the compact_day_plan function
+    #[test]
+    fn test_compact_day_plan() {
+        let mut calendar = DoctorsCalendar::new();
+
+        for (date_time, appointment_type) in [
+            (at(9, 30), AppointmentType::DentalCheckUp),
+            (at(13, 15), AppointmentType::ImplantConsultation),
+            (at(16, 30), AppointmentType::DentalCheckUp),
+        ] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(date_time, appointment_type))
+                .unwrap();
+        }
+
+        let plan: Vec<(NaiveDateTime, NaiveDateTime)> = calendar
+            .compact_day_plan(at(0, 0).date())
+            .into_iter()
+            .map(|(appointment, start)| (appointment.date_time, start))
+            .collect();
+
+        assert_eq!(
+            plan,
+            vec![
+                (at(9, 30), at(8, 0)),
+                (at(13, 15), at(8, 30)),
+                (at(16, 30), at(10, 0)),
+            ]
+        );
+
+        // The calendar isn't changed
+        assert_eq!(calendar.appointments().next().unwrap().date_time, at(9, 30));
+
+        // The last appointment doesn't fit before the break anymore, so it moves
+        // after it
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(11, 0),
+                AppointmentType::UrgentDentalAppointment,
+            ))
+            .unwrap();
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(14, 45),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+        let plan: Vec<NaiveDateTime> = calendar
+            .compact_day_plan(at(0, 0).date())
+            .into_iter()
+            .map(|(_, start)| start)
+            .collect();
+        assert_eq!(
+            plan,
+            vec![at(8, 0), at(8, 30), at(8, 45), at(10, 15), at(13, 0)]
+        );
+    }
+
+    // Test the is_same_appointment function
+    #[test]
+    fn test_is_same_appointment() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let date_time = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let appointment = DoctorsAppointment::new(date_time, AppointmentType::DentalCheckUp);
+        assert!(!appointment.is_same_appointment(&appointment.clone()));
+
+        calendar.add_appointment(appointment.clone()).unwrap();
+
+        // A second appointment at the same start is rejected, even with another
+        // type or patient
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                date_time,
+                AppointmentType::UrgentDentalAppointment
+            )),
+            Err(CalendarError::Overlap)
+        );
+        assert_eq!(
+            calendar.add_appointment(
+                appointment
+                    .clone()
+                    .with_patient(Patient::new("Jane Doe", "555-0100"))
+            ),
+            Err(CalendarError::Overlap)
+        );
+
+        // The booked appointment equals the added value, and keeps its identity
+        // when its value changes
+        let booked = calendar.appointments().next().cloned().unwrap();
+        assert_eq!(booked, appointment);
+
+        calendar
+            .set_status(date_time, AppointmentStatus::Completed)
+            .unwrap();
+        let completed = calendar.appointments().next().cloned().unwrap();
+        assert_ne!(completed, booked);
+        assert!(completed.is_same_appointment(&booked));
+        assert_eq!(calendar.len(), 1);
+    }
+
+    // Test the next_recurrence_slot function
+    #[test]
+    fn test_next_recurrence_slot() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Thursday
+        let previous = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+        );
+
+        // Four weeks later is a Thursday too
+        assert_eq!(
+            calendar.next_recurrence_slot(
+                previous,
+                AppointmentType::DentalCheckUp,
+                Duration::weeks(4)
+            ),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap()
+            ))
+        );
+
+        // 30 days later is a Saturday, so the follow-up moves to Monday morning
+        assert_eq!(
+            calendar.next_recurrence_slot(
+                previous,
+                AppointmentType::DentalCheckUp,
+                Duration::days(30)
+            ),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+            ))
+        );
+
+        let at = |hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        // The target time is booked by a check-up, so the free time slots
+        // right before and after it are equally close, and the later one wins
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.next_recurrence_slot(
+                previous,
+                AppointmentType::DentalCheckUp,
+                Duration::weeks(4)
+            ),
+            Some(at(10, 30))
+        );
+
+        // Booked by an implant consultation instead, the earlier free time
+        // slot is the closest
+        calendar.cancel_appointment(at(10, 0)).unwrap();
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 0),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.next_recurrence_slot(
+                previous,
+                AppointmentType::DentalCheckUp,
+                Duration::weeks(4)
+            ),
+            Some(at(9, 30))
+        );
+    }
+
+    // Test the conflicts_for function
+    #[test]
+    fn test_conflicts_for() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let check_up = DoctorsAppointment::new(at(8, 30), AppointmentType::DentalCheckUp);
+        let urgent = DoctorsAppointment::new(at(9, 15), AppointmentType::UrgentDentalAppointment);
+        let later = DoctorsAppointment::new(at(9, 30), AppointmentType::DentalCheckUp);
+        for appointment in [&check_up, &urgent, &later] {
+            calendar.add_appointment(appointment.clone()).unwrap();
+        }
+
+        // The 90 minute implant consultation from 8:00 to 9:30 overlaps with the
+        // first two appointments
+        let proposed = DoctorsAppointment::new(at(8, 0), AppointmentType::ImplantConsultation);
+        assert_eq!(calendar.conflicts_for(&proposed), vec![check_up, urgent]);
+
+        let proposed = DoctorsAppointment::new(at(10, 0), AppointmentType::DentalCheckUp);
+        assert!(calendar.conflicts_for(&proposed).is_empty());
+    }
+
+    // Test the force_book function
+    #[test]
+    fn test_force_book() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let check_up = DoctorsAppointment::new(at(9, 0), AppointmentType::DentalCheckUp);
+        calendar.add_appointment(check_up.clone()).unwrap();
+
+        // The urgent appointment is double-booked
+        let urgent = DoctorsAppointment::new(at(9, 15), AppointmentType::UrgentDentalAppointment);
+        assert_eq!(
+            calendar.force_book(urgent.clone()),
+            Ok(vec![check_up.clone()])
+        );
+        assert_eq!(calendar.len(), 2);
+        assert!(calendar
+            .appointments()
+            .any(|appointment| *appointment == urgent));
+        assert!(calendar
+            .appointments()
+            .any(|appointment| *appointment == check_up));
+
+        // Both are reported as conflicts of a later booking
+        let implant = DoctorsAppointment::new(at(8, 0), AppointmentType::ImplantConsultation);
+        assert_eq!(
+            calendar.conflicts_for(&implant),
+            vec![check_up, urgent.clone()]
+        );
+
+        // The working hours still apply
+        assert_eq!(
+            calendar.force_book(DoctorsAppointment::new(
+                at(11, 45),
+                AppointmentType::DentalCheckUp
+            )),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+
+        // Without overlap, it's a regular booking
+        assert_eq!(
+            calendar.force_book(DoctorsAppointment::new(
+                at(13, 0),
+                AppointmentType::DentalCheckUp
+            )),
+            Ok(vec![])
+        );
+        assert_eq!(calendar.len(), 3);
+
+        // Two identical appointments are both kept, and both conflict
+        let urgent = DoctorsAppointment::new(at(14, 0), AppointmentType::UrgentDentalAppointment);
+        calendar.force_book(urgent.clone()).unwrap();
+        assert_eq!(
+            calendar.force_book(urgent.clone()),
+            Ok(vec![urgent.clone()])
+        );
+        assert_eq!(calendar.len(), 5);
+        assert_eq!(calendar.conflicts_for(&urgent).len(), 2);
+
+        // Cancelling one of them leaves the other one in place
+        calendar.cancel_appointment(at(14, 0)).unwrap();
+        assert_eq!(calendar.conflicts_for(&urgent), vec![urgent.clone()]);
+        assert!(!calendar.is_slot_free(at(14, 0)));
+        assert_eq!(
+            calendar.add_appointment(urgent),
+            Err(CalendarError::Overlap)
+        );
+
+        // The double bookings survive an export and import
+        let mut output = vec![];
+        calendar.export_jsonl(&mut output).unwrap();
+
+        let mut imported_calendar = DoctorsCalendar::new();
+        assert_eq!(
+            imported_calendar
+                .import_jsonl(output.as_slice(), RoundingPolicy::Reject)
+                .unwrap(),
+            calendar.len()
+        );
+        assert!(imported_calendar.appointments().eq(calendar.appointments()));
+        assert_eq!(
+            imported_calendar.conflicts_for(&implant),
+            calendar.conflicts_for(&implant)
+        );
+    }
+
+    // Test the freebusy_json function
+    #[test]
+    fn test_freebusy_json() {
+        let mut calendar = DoctorsCalendar::new();
+
+        for (hour, minute) in [(8, 0), (8, 30), (14, 0)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    at(hour, minute),
+                    AppointmentType::DentalCheckUp,
+                ))
+                .unwrap();
+        }
+        calendar.add_block(Block::new(at(16, 0), at(17, 0), "Meeting"));
+
+        // Central European Time
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let free_busy: FreeBusy =
+            serde_json::from_str(&calendar.freebusy_json(at(0, 0), at(23, 45), offset)).unwrap();
+
+        let interval = |start, end| FreeBusyInterval::new(start, end, offset);
+        assert_eq!(
+            free_busy.busy,
+            vec![
+                interval(at(8, 0), at(9, 0)),
+                interval(at(14, 0), at(14, 30)),
+                interval(at(16, 0), at(17, 0)),
+            ]
+        );
+        assert_eq!(
+            free_busy.free,
+            vec![
+                interval(at(9, 0), at(12, 0)),
+                interval(at(13, 0), at(14, 0)),
+                interval(at(14, 30), at(16, 0)),
+            ]
+        );
+
+        // The timestamps are RFC 3339 with the practice's offset, so the first
+        // busy interval starts at 7:00 UTC
+        let json: serde_json::Value =
+            serde_json::from_str(&calendar.freebusy_json(at(0, 0), at(23, 45), offset)).unwrap();
+        assert_eq!(json["busy"][0]["start"], "2024-02-01T08:00:00+01:00");
+        assert_eq!(json["free"][0]["end"], "2024-02-01T12:00:00+01:00");
+        assert_eq!(free_busy.busy[0].start.naive_utc(), at(7, 0));
+
+        // Together they tile the working hours
+        let mut intervals = [free_busy.busy, free_busy.free].concat();
+        intervals.sort_by_key(|interval| interval.start);
+        let mut tiled = vec![];
+        for interval in intervals {
+            match tiled.last_mut() {
+                Some(FreeBusyInterval { end, .. }) if *end == interval.start => *end = interval.end,
+                _ => tiled.push(interval),
+            }
+        }
+        assert_eq!(
+            tiled,
+            vec![
+                interval(at(8, 0), at(12, 0)),
+                interval(at(13, 0), at(17, 0))
+            ]
+        );
+    }
+
+    // Test the set_overlap_tolerance function
+    #[test]
+    fn test_overlap_tolerance() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // From 8:00 to 8:30
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(8, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+
+        // Overlapping by 5 minutes
+        let cleaning = DoctorsAppointment::new(at(8, 25), AppointmentType::DentalCheckUp);
+        assert_eq!(
+            calendar.clone().add_appointment(cleaning.clone()),
+            Err(CalendarError::Overlap)
+        );
+
+        calendar
+            .set_overlap_tolerance(Duration::minutes(5))
+            .unwrap();
+        assert_eq!(calendar.clone().add_appointment(cleaning), Ok(()));
+
+        // Overlapping by 10 minutes is still rejected
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(8, 20),
+                AppointmentType::DentalCheckUp
+            )),
+            Err(CalendarError::Overlap)
+        );
+
+        // Back-to-back appointments don't overlap at all
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(8, 30),
+                AppointmentType::DentalCheckUp
+            )),
+            Ok(())
+        );
+
+        // The tolerance must be shorter than a time slot
+        assert_eq!(
+            calendar.set_overlap_tolerance(Duration::minutes(15)),
+            Err(CalendarError::InvalidDuration)
+        );
+        assert_eq!(calendar.overlap_tolerance(), Duration::minutes(5));
+    }
+
+    // Test the appointments_outside_schedule function
+    #[test]
+    fn test_appointments_outside_schedule() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let morning = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            ),
+            AppointmentType::DentalCheckUp,
+        );
+        let afternoon = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(15, 30, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        calendar.add_appointment(morning.clone()).unwrap();
+        calendar.add_appointment(afternoon.clone()).unwrap();
+        assert!(calendar.appointments_outside_schedule().is_empty());
+
+        // The practice now closes at 16:00
+        calendar.set_schedule(
+            WorkingSchedule::continuous(
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+            )
+            .unwrap(),
+        );
+        assert_eq!(calendar.appointments_outside_schedule(), vec![afternoon]);
+        assert_eq!(calendar.len(), 2);
+    }
+
+    // Test the restore_jsonl function after the working hours changed
+    #[test]
+    fn test_restore_jsonl() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let afternoon = DoctorsAppointment::new(
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(15, 30, 0).unwrap(),
+            ),
+            AppointmentType::ImplantConsultation,
+        );
+        calendar.add_appointment(afternoon.clone()).unwrap();
+
+        // The practice now closes at 16:00, and the calendar is saved
+        let schedule = WorkingSchedule::continuous(
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        )
+        .unwrap();
+        calendar.set_schedule(schedule.clone());
+
+        let mut output = vec![];
+        calendar.export_jsonl(&mut output).unwrap();
+
+        // Importing validates the working hours, restoring keeps the appointment
+        let mut loaded_calendar = DoctorsCalendar::new();
+        loaded_calendar.set_schedule(schedule);
+        assert!(loaded_calendar
+            .import_jsonl(output.as_slice(), RoundingPolicy::Reject)
+            .is_err());
+
+        assert_eq!(loaded_calendar.restore_jsonl(output.as_slice()).unwrap(), 1);
+        assert!(loaded_calendar.appointments().eq(calendar.appointments()));
+        assert_eq!(
+            loaded_calendar.appointments_outside_schedule(),
+            vec![afternoon]
+        );
+
+        // Ids already in use are still rejected
+        assert!(loaded_calendar.restore_jsonl(output.as_slice()).is_err());
+        assert_eq!(loaded_calendar.len(), 1);
+    }
+
+    // Test the idle_time function
+    #[test]
+    fn test_idle_time() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let to = from + Duration::days(1);
+
+        assert_eq!(calendar.idle_time(from, to), Duration::hours(8));
+
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+                ),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.idle_time(from, to),
+            Duration::hours(7) + Duration::minutes(30)
+        );
+
+        // Weekend
+        assert_eq!(
+            calendar.idle_time(from + Duration::days(2), to + Duration::days(2)),
+            Duration::zero()
+        );
+    }
+
+    // Test the fill_realistic function
+    #[test]
+    fn test_fill_realistic() {
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 5).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 9).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+
+        // The placement is random, so try a few times
+        for _ in 0..10 {
+            let mut calendar = DoctorsCalendar::new();
+
+            // Implants dominate, which leaves no single time slot gaps
+            calendar.fill_realistic(
+                from,
+                to,
+                &BTreeMap::from([(AppointmentType::ImplantConsultation, 100)]),
+            );
+            assert!(calendar.len() >= 16);
+            assert!(calendar
+                .free_run_lengths(from, to)
+                .iter()
+                .all(|(_, length)| *length % 2 == 0));
+
+            // So the check-ups fill the remaining time
+            calendar.fill_realistic(
+                from,
+                to,
+                &BTreeMap::from([(AppointmentType::DentalCheckUp, 100)]),
+            );
+            assert!(calendar.free_run_lengths(from, to).is_empty());
+        }
+    }
+
+    // Test the working_slots_until function
+    #[test]
+    fn test_working_slots_until() {
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        );
+
+        // A full working day has 32 time slots, skipping the break
+        let slots: Vec<NaiveDateTime> = from.working_slots_until(to).collect();
+        assert_eq!(slots.len(), 32);
+        assert_eq!(slots[0].time(), NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(
+            slots[15].time(),
+            NaiveTime::from_hms_opt(11, 45, 0).unwrap()
+        );
+        assert_eq!(slots[16].time(), NaiveTime::from_hms_opt(13, 0, 0).unwrap());
+        assert_eq!(
+            slots[31].time(),
+            NaiveTime::from_hms_opt(16, 45, 0).unwrap()
+        );
+
+        // The start is included if it's a working time slot, the end never is
+        let start = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let end = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        );
+        assert_eq!(start.working_slots_until(end).count(), 16);
+        assert_eq!(start.working_slots_until(start).count(), 0);
+
+        // So filling the morning completely books every time slot of it
+        let mut calendar = DoctorsCalendar::new();
+
+        calendar.fill_random(start, end, AppointmentType::UrgentDentalAppointment, 100);
+
+        assert_eq!(calendar.len(), 16);
+        assert_eq!(calendar.total_working_slots(start, end), 16);
+    }
+
+    // Test the longest_free_window function
+    #[test]
+    fn test_longest_free_window() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+
+        // An empty day has two 4 hour windows, the morning comes first
+        assert_eq!(
+            calendar.longest_free_window(date),
+            Some((at(8, 0), Duration::hours(4)))
+        );
+
+        // A morning appointment leaves the afternoon as the longest window
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.longest_free_window(date),
+            Some((at(13, 0), Duration::hours(4)))
+        );
+
+        // Weekends have no working time
+        let saturday = NaiveDate::from_ymd_opt(2024, 2, 3).unwrap();
+        assert_eq!(calendar.longest_free_window(saturday), None);
+    }
+
+    // Test the due_reminders function
+    #[test]
+    fn test_due_reminders() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = |day, hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        for date_time in [at(6, 9, 45), at(6, 10, 0), at(6, 10, 15), at(6, 10, 30)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    date_time,
+                    AppointmentType::UrgentDentalAppointment,
+                ))
+                .unwrap();
+        }
+
+        // Only the appointment starting a day after now is reminded
+        let now = at(5, 10, 0);
+        let lead = Duration::hours(24);
+        let reminders = calendar.due_reminders(now, lead);
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].date_time, at(6, 10, 0));
+
+        // Also when the reminders run late within the time slot
+        let reminders = calendar.due_reminders(at(5, 10, 5), lead);
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].date_time, at(6, 10, 15));
+
+        // Cancelled appointments are not reminded
+        calendar
+            .set_status(at(6, 10, 0), AppointmentStatus::Cancelled)
+            .unwrap();
+        assert!(calendar.due_reminders(now, lead).is_empty());
+        assert!(calendar.due_reminders(at(5, 12, 0), lead).is_empty());
+    }
+
+    // Test the week function
+    #[test]
+    fn test_week() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = |day, hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        // Friday of the previous week, Monday and Friday of the week, and
+        // Monday of the next week
+        for date_time in [at(2, 16, 45), at(5, 8, 0), at(9, 16, 45), at(12, 8, 0)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    date_time,
+                    AppointmentType::UrgentDentalAppointment,
+                ))
+                .unwrap();
+        }
+
+        // Any day of the week gives the same appointments, weekend included
+        for day in 5..=11 {
+            let week = calendar.week(NaiveDate::from_ymd_opt(2024, 2, day).unwrap());
+            assert_eq!(
+                week.iter()
+                    .map(|appointment| appointment.date_time)
+                    .collect::<Vec<_>>(),
+                vec![at(5, 8, 0), at(9, 16, 45)]
+            );
+        }
+
+        assert!(calendar
+            .week(NaiveDate::from_ymd_opt(2024, 2, 19).unwrap())
+            .is_empty());
+    }
+
+    // Test the extend_day function
+    #[test]
+    fn test_extend_day() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = |day, hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        calendar
+            .extend_day(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                (
+                    NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+                    NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                ),
+            )
+            .unwrap();
+
+        // The extended day has 4 more time slots, the next day is unchanged
+        assert_eq!(calendar.total_working_slots(at(1, 0, 0), at(2, 0, 0)), 36);
+        assert_eq!(calendar.total_working_slots(at(2, 0, 0), at(3, 0, 0)), 32);
+        assert_eq!(
+            calendar.available_single_time_slots(at(1, 17, 0), at(2, 8, 30)),
+            vec![
+                at(1, 17, 0),
+                at(1, 17, 15),
+                at(1, 17, 30),
+                at(1, 17, 45),
+                at(2, 8, 0),
+                at(2, 8, 15)
+            ]
+        );
+
+        // Evening appointments can only be booked on the extended day, also
+        // running from the afternoon into the evening
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(1, 16, 30),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(2, 17, 0),
+                AppointmentType::DentalCheckUp,
+            )),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+
+        // The one-off window must not be empty
+        assert!(calendar
+            .extend_day(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                (
+                    NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                    NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+                ),
+            )
+            .is_err());
+    }
+
+    // Test the window_density function
+    #[test]
+    fn test_window_density() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // A 90 minute implant fills the 9:00 window and half of the 10:00 one
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(9, 0),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+
+        let density = calendar.window_density(at(8, 0), at(17, 0), Duration::minutes(60));
+        assert_eq!(density.len(), 8);
+        assert_eq!(density[&at(8, 0)], (0, 4));
+        assert_eq!(density[&at(9, 0)], (4, 4));
+        assert_eq!(density[&at(10, 0)], (2, 4));
+        assert_eq!(density[&at(11, 0)], (0, 4));
+        assert!(!density.contains_key(&at(12, 0)));
+
+        // The windows can have any size
+        let density = calendar.window_density(at(8, 0), at(12, 0), Duration::minutes(120));
+        assert_eq!(
+            density,
+            BTreeMap::from([(at(8, 0), (4, 8)), (at(10, 0), (2, 8))])
+        );
+        assert!(calendar
+            .window_density(at(8, 0), at(12, 0), Duration::zero())
+            .is_empty());
+    }
+
+    // Test the archived_appointments function
+    #[test]
+    fn test_archived_appointments() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = |day, hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        for date_time in [at(1, 9, 0), at(1, 10, 0), at(2, 9, 0)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    date_time,
+                    AppointmentType::DentalCheckUp,
+                ))
+                .unwrap();
+        }
+
+        calendar.cancel_appointment(at(1, 10, 0)).unwrap();
+        calendar.cancel_range(at(2, 0, 0), at(2, 23, 59));
+
+        // The time slots of the archived appointments are free again
+        assert!(calendar.is_slot_free(at(1, 10, 0)));
+        assert!(calendar.is_slot_free(at(2, 9, 0)));
+        assert_eq!(calendar.booked_appointments(None, None).len(), 1);
+
+        // But they are still in the archive
+        let archived = calendar.archived_appointments(None, None);
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[0].0.date_time, at(1, 10, 0));
+        assert_eq!(archived[0].1, AppointmentStatus::Cancelled);
+        assert_eq!(archived[1].0.date_time, at(2, 9, 0));
+
+        let archived = calendar.archived_appointments(Some(at(2, 0, 0)), None);
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].0.date_time, at(2, 9, 0));
+    }
+
+    // Test the add_closure function
+    #[test]
+    fn test_add_closure() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = |day, hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        // Close the afternoon of the 1st and the whole 2nd
+        calendar.add_closure(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            Some((
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            )),
+        );
+        let closure = calendar.add_closure(NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(), None);
+
+        // Only the morning of the 1st is left
+        let available_time_slots = calendar.available_single_time_slots(at(1, 0, 0), at(3, 0, 0));
+        assert_eq!(available_time_slots.len(), 16);
+        assert_eq!(available_time_slots.first(), Some(&at(1, 8, 0)));
+        assert_eq!(available_time_slots.last(), Some(&at(1, 11, 45)));
+
+        assert!(calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(1, 11, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .is_ok());
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(1, 14, 0),
+                AppointmentType::DentalCheckUp,
+            )),
+            Err(CalendarError::Blocked)
+        );
+
+        // Lifting the closure opens the day again
+        assert!(calendar.remove_block(&closure));
+        assert_eq!(
+            calendar
+                .available_single_time_slots(at(2, 0, 0), at(3, 0, 0))
+                .len(),
+            32
+        );
+    }
+
+    // Test the appointments_grouped_by_type function
+    #[test]
+    fn test_appointments_grouped_by_type() {
+        let mut calendar = DoctorsCalendar::new();
+
+        for (date_time, appointment_type) in [
+            (at(8, 0), AppointmentType::UrgentDentalAppointment),
+            (at(8, 15), AppointmentType::DentalCheckUp),
+            (at(9, 0), AppointmentType::ImplantConsultation),
+            (at(10, 30), AppointmentType::UrgentDentalAppointment),
+            (at(13, 0), AppointmentType::ImplantConsultation),
+        ] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(date_time, appointment_type))
+                .unwrap();
+        }
+
+        let groups = calendar.appointments_grouped_by_type(None, None);
+
+        // The implants are listed first, and each group is chronological
+        let start_times: Vec<(AppointmentType, Vec<NaiveDateTime>)> = groups
+            .into_iter()
+            .map(|(appointment_type, appointments)| {
+                (
+                    appointment_type,
+                    appointments
+                        .iter()
+                        .map(|appointment| appointment.date_time)
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            start_times,
+            vec![
+                (
+                    AppointmentType::ImplantConsultation,
+                    vec![at(9, 0), at(13, 0)]
+                ),
+                (AppointmentType::DentalCheckUp, vec![at(8, 15)]),
+                (
+                    AppointmentType::UrgentDentalAppointment,
+                    vec![at(8, 0), at(10, 30)]
+                ),
+            ]
+        );
+
+        // Only the appointments within the time period are grouped
+        let groups = calendar.appointments_grouped_by_type(Some(at(10, 0)), Some(at(12, 0)));
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[&AppointmentType::UrgentDentalAppointment].len(), 1);
+    }
+
+    // Test the is_consistent and validate functions
+    #[test]
+    fn test_is_consistent() {
+        let mut calendar = DoctorsCalendar::new();
+
+        assert!(calendar.is_consistent());
+
+        for (date_time, appointment_type) in [
+            (at(8, 0), AppointmentType::DentalCheckUp),
+            (at(15, 30), AppointmentType::ImplantConsultation),
+        ] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(date_time, appointment_type))
+                .unwrap();
+        }
+        assert!(calendar.is_consistent());
+
+        // Shortening the working hours leaves the afternoon implant outside
+        calendar.set_schedule(
+            WorkingSchedule::with_break(
+                NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+                Duration::hours(1),
+            )
+            .unwrap(),
+        );
+        assert!(!calendar.is_consistent());
+
+        let errors = calendar.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0.date_time, at(15, 30));
+        assert_eq!(errors[0].1, CalendarError::OutsideWorkingHours);
+
+        // Cancelled appointments don't count
+        calendar
+            .set_status(at(15, 30), AppointmentStatus::Cancelled)
+            .unwrap();
+        assert_eq!(calendar.validate(), Ok(()));
+
+        // A double booking overlaps with the morning check-up
+        calendar
+            .force_book(DoctorsAppointment::new(
+                at(8, 15),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert!(!calendar.is_consistent());
+
+        let errors = calendar.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0.date_time, at(8, 0));
+        assert_eq!(errors[1].0.date_time, at(8, 15));
+        assert!(errors.iter().all(|(_, e)| *e == CalendarError::Overlap));
+
+        // Identical appointments overlap too
+        let mut calendar = DoctorsCalendar::new();
+        let urgent = DoctorsAppointment::new(at(9, 0), AppointmentType::UrgentDentalAppointment);
+        calendar.force_book(urgent.clone()).unwrap();
+        calendar.force_book(urgent).unwrap();
+        assert!(!calendar.is_consistent());
+        assert_eq!(calendar.validate().unwrap_err().len(), 2);
+        assert_eq!(calendar.overlap_report().lines().count(), 1);
+    }
+
+    // Test the allowed windows per appointment type
+    #[test]
+    fn test_allowed_windows() {
+        let mut calendar = DoctorsCalendar::new();
+        calendar.set_catalog(AppointmentCatalog::new().with_allowed_window(
+            AppointmentType::ImplantConsultation,
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+        ));
+
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        // Implants are only offered in the morning, check-ups all day
+        let implant_slots = calendar.free_slots(from, to, AppointmentType::ImplantConsultation);
+        assert_eq!(implant_slots.first(), Some(&at(8, 0)));
+        assert_eq!(implant_slots.last(), Some(&at(10, 30)));
+
+        let check_up_slots = calendar.free_slots(from, to, AppointmentType::DentalCheckUp);
+        assert!(check_up_slots.contains(&at(13, 0)));
+        assert_eq!(check_up_slots.last(), Some(&at(16, 30)));
+
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(13, 0),
+                AppointmentType::ImplantConsultation
+            )),
+            Err(CalendarError::OutsideAllowedWindow)
+        );
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(13, 0),
+                AppointmentType::DentalCheckUp
+            )),
+            Ok(())
+        );
+    }
+
+    // Test the lead_time_histogram function
+    #[test]
+    fn test_lead_time_histogram() {
+        let now = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let mut calendar = DoctorsCalendar::with_clock(Arc::new(FixedClock(now)));
+
+        let at = |day, hour| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            )
+        };
+
+        // Booked 2 hours, 1 day and 2 weeks in advance
+        for date_time in [at(1, 10), at(2, 8), at(15, 8)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    date_time,
+                    AppointmentType::DentalCheckUp,
+                ))
+                .unwrap();
+        }
+
+        // Booked 6 days in advance, e.g. imported from another system
+        let mut imported = DoctorsAppointment::new(at(8, 9), AppointmentType::DentalCheckUp);
+        imported.created_at = Some(at(2, 9));
+        calendar.add_appointment(imported).unwrap();
+
+        // The creation time is taken from the clock, unless already set
+        let created_at: Vec<Option<NaiveDateTime>> = calendar
+            .iter()
+            .map(|appointment| appointment.created_at)
+            .collect();
+        assert_eq!(
+            created_at,
+            vec![Some(now), Some(now), Some(at(2, 9)), Some(now)]
+        );
+        assert_eq!(
+            calendar.lead_time_histogram(&[
+                Duration::days(1),
+                Duration::weeks(1),
+                Duration::weeks(4)
+            ]),
+            vec![1, 2, 1, 0]
+        );
+        assert_eq!(calendar.lead_time_histogram(&[]), vec![4]);
+    }
+
+    // Test the cancel_appointment_with_reason function
+    #[test]
+    fn test_cancel_appointment_with_reason() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let at = |day, hour| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            )
+        };
+
+        for date_time in [at(1, 9), at(1, 10), at(1, 11), at(2, 9)] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(
+                    date_time,
+                    AppointmentType::DentalCheckUp,
+                ))
+                .unwrap();
+        }
+
+        calendar
+            .cancel_appointment_with_reason(at(1, 9), CancellationReason::NoShow)
+            .unwrap();
+        calendar
+            .cancel_appointment_with_reason(at(1, 10), CancellationReason::ClinicClosure)
+            .unwrap();
+        calendar.cancel_appointment(at(1, 11)).unwrap();
+        assert_eq!(
+            calendar.cancel_appointment_with_reason(at(1, 12), CancellationReason::NoShow),
+            Err(CalendarError::NotFound)
+        );
+
+        // Only the no-show counts as one
+        assert_eq!(calendar.no_show_count(None, None), 1);
+        assert_eq!(
+            calendar
+                .archived_appointments(None, None)
+                .iter()
+                .map(|(_, status)| *status)
+                .collect::<Vec<_>>(),
+            vec![
+                AppointmentStatus::NoShow,
+                AppointmentStatus::Cancelled,
+                AppointmentStatus::Cancelled
+            ]
+        );
+
+        // Cancelling a range counts as a clinic closure
+        calendar.cancel_range(at(2, 0), at(2, 23));
+        assert_eq!(
+            calendar.cancellations_by_reason(None, None),
+            BTreeMap::from([
+                (CancellationReason::PatientRequest, 1),
+                (CancellationReason::ClinicClosure, 2),
+                (CancellationReason::NoShow, 1),
+            ])
+        );
+        assert_eq!(
+            calendar.cancellations_by_reason(Some(at(2, 0)), None),
+            BTreeMap::from([(CancellationReason::ClinicClosure, 1)])
+        );
+    }
+
+    // Test the free_slots_spans function
+    #[test]
+    fn test_free_slots_spans() {
+        let calendar = DoctorsCalendar::new();
+
+        let spans = calendar.free_slots_spans(
+            Some(at(8, 0)),
+            Some(at(12, 0)),
+            AppointmentType::ImplantConsultation,
+        );
+
+        // Every implant span is 90 minutes long, and none crosses the break
+        assert_eq!(spans.first(), Some(&(at(8, 0), at(9, 30))));
+        assert_eq!(spans.last(), Some(&(at(10, 30), at(12, 0))));
+        assert!(spans
+            .iter()
+            .all(|(start, end)| *end - *start == Duration::minutes(90)));
+        assert_eq!(
+            spans.iter().map(|(start, _)| *start).collect::<Vec<_>>(),
+            calendar.free_slots(
+                Some(at(8, 0)),
+                Some(at(12, 0)),
+                AppointmentType::ImplantConsultation
+            )
+        );
+    }
+
+    // Test the add_appointment_versioned function
+    #[test]
+    fn test_add_appointment_versioned() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Two clients read the same version
+        let read_version = calendar.version();
+
+        let version = calendar
+            .add_appointment_versioned(
+                DoctorsAppointment::new(at(9, 0), AppointmentType::DentalCheckUp),
+                read_version,
+            )
+            .unwrap();
+        assert!(version > read_version);
+        assert_eq!(calendar.version(), version);
+
+        // The second client's booking is rejected, even for another time slot
+        assert_eq!(
+            calendar.add_appointment_versioned(
+                DoctorsAppointment::new(at(10, 0), AppointmentType::DentalCheckUp),
+                read_version,
+            ),
+            Err(CalendarError::StaleVersion {
+                expected: read_version,
+                current: version,
+            })
+        );
+
+        // Until it reads the calendar again
+        assert!(calendar
+            .add_appointment_versioned(
+                DoctorsAppointment::new(at(10, 0), AppointmentType::DentalCheckUp),
+                calendar.version(),
+            )
+            .is_ok());
+
+        // Failed bookings keep the version, other changes move it
+        let version = calendar.version();
+        assert!(calendar
+            .add_appointment_versioned(
+                DoctorsAppointment::new(at(9, 0), AppointmentType::DentalCheckUp),
+                version,
+            )
+            .is_err());
+        assert_eq!(calendar.version(), version);
+
+        calendar.cancel_appointment(at(9, 0)).unwrap();
+        assert!(calendar.version() > version);
+
+        // So do the booking rules
+        let version = calendar.version();
+        calendar.set_catalog(
+            AppointmentCatalog::new()
+                .with_duration(AppointmentType::DentalCheckUp, Duration::minutes(45)),
+        );
+        assert!(calendar.version() > version);
+        assert_eq!(
+            calendar.add_appointment_versioned(
+                DoctorsAppointment::new(at(11, 0), AppointmentType::DentalCheckUp),
+                version,
+            ),
+            Err(CalendarError::StaleVersion {
+                expected: version,
+                current: calendar.version(),
+            })
+        );
+
+        let version = calendar.version();
+        calendar
+            .set_overlap_tolerance(Duration::minutes(5))
+            .unwrap();
+        assert!(calendar.version() > version);
+
+        let version = calendar.version();
+        calendar
+            .set_optimizer_window(Duration::minutes(30))
+            .unwrap();
+        assert!(calendar.version() > version);
+    }
+
+    /// Book a day of 10 appointments, half of them implants, on each of the
+    /// given number of working days from 2024-01-01
+    fn book_working_days(calendar: &mut DoctorsCalendar, working_days: usize) {
+        let day_plan = [
+            (8, 0, AppointmentType::ImplantConsultation),
+            (9, 30, AppointmentType::ImplantConsultation),
+            (11, 0, AppointmentType::DentalCheckUp),
+            (11, 30, AppointmentType::UrgentDentalAppointment),
+            (11, 45, AppointmentType::UrgentDentalAppointment),
+            (13, 0, AppointmentType::ImplantConsultation),
+            (14, 30, AppointmentType::ImplantConsultation),
+            (16, 0, AppointmentType::DentalCheckUp),
+            (16, 30, AppointmentType::UrgentDentalAppointment),
+            (16, 45, AppointmentType::UrgentDentalAppointment),
+        ];
+
+        let mut date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut days = 0;
+
+        while days < working_days {
+            if calendar.schedule().is_working_day(date) {
+                for (hour, minute, appointment_type) in day_plan {
+                    calendar
+                        .add_appointment(DoctorsAppointment::new(
+                            NaiveDateTime::new(
+                                date,
+                                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+                            ),
+                            appointment_type,
+                        ))
+                        .unwrap();
+                }
+
+                days += 1;
+            }
+
+            date += Duration::days(1);
+        }
+    }
+
+    /// Get 20 proposed implant consultations from 2024-01-01, the given number
+    /// of minutes apart
+    fn proposed_implants(spacing_minutes: i64) -> Vec<DoctorsAppointment> {
+        (0..20)
+            .map(|index| {
+                DoctorsAppointment::new(
+                    NaiveDateTime::new(
+                        NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                        NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                    ) + Duration::minutes(index * spacing_minutes),
+                    AppointmentType::ImplantConsultation,
+                )
+            })
+            .collect()
+    }
+
+    /// Find the conflicts of the proposed appointments by scanning the whole
+    /// calendar
+    fn naive_conflicts(
+        calendar: &DoctorsCalendar,
+        proposed: &[DoctorsAppointment],
+    ) -> Vec<Vec<DoctorsAppointment>> {
+        proposed
+            .iter()
+            .map(|appointment| {
+                calendar
+                    .iter()
+                    .filter(|existing| {
+                        existing.date_time < appointment.end_time()
+                            && existing.end_time() > appointment.date_time
+                    })
+                    .cloned()
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Compare the overlap queries of the interval index with a naive scan
+    #[test]
+    fn test_interval_index() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // 5000 appointments over about 2 years
+        book_working_days(&mut calendar, 500);
+        assert_eq!(calendar.len(), 5000);
+
+        // Proposed appointments spread over the whole calendar
+        let proposed = proposed_implants(50_015);
+
+        let indexed: Vec<Vec<DoctorsAppointment>> = proposed
+            .iter()
+            .map(|appointment| calendar.conflicts_for(appointment))
+            .collect();
+
+        // Both find the same conflicts, long appointments included
+        assert_eq!(indexed, naive_conflicts(&calendar, &proposed));
+        assert!(indexed.iter().any(|conflicts| conflicts.len() > 1));
+
+        // The busy time of a week is found the same way
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2025, 6, 2).unwrap(),
+            NaiveTime::from_hms_opt(8, 45, 0).unwrap(),
+        );
+        let to = from + Duration::weeks(1);
+        let busy_intervals = calendar.busy_intervals(from, to);
+        assert_eq!(busy_intervals.len(), 11);
+        assert_eq!(busy_intervals[0], (from, from + Duration::minutes(195)));
+    }
+
+    // Benchmark the overlap queries of the interval index against a naive scan
+    // with 50,000 appointments. Run it with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore = "benchmark, timings depend on the machine"]
+    fn test_interval_index_benchmark() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // 50,000 appointments over about 19 years
+        book_working_days(&mut calendar, 5000);
+        assert_eq!(calendar.len(), 50_000);
+
+        let proposed = proposed_implants(500_015);
+
+        let started = std::time::Instant::now();
+        let indexed: Vec<Vec<DoctorsAppointment>> = proposed
+            .iter()
+            .map(|appointment| calendar.conflicts_for(appointment))
+            .collect();
+        let indexed_time = started.elapsed();
+
+        let started = std::time::Instant::now();
+        let naive = naive_conflicts(&calendar, &proposed);
+        let naive_time = started.elapsed();
+
+        println!("indexed: {:?}, naive scan: {:?}", indexed_time, naive_time);
+
+        assert_eq!(indexed, naive);
+        assert!(indexed_time < naive_time);
+    }
+
+    // Test the shift_range function
+    #[test]
+    fn test_shift_range() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // A back-to-back afternoon, and a morning appointment that stays
+        for (date_time, appointment_type) in [
+            (at(11, 0), AppointmentType::DentalCheckUp),
+            (at(13, 0), AppointmentType::ImplantConsultation),
+            (at(14, 30), AppointmentType::DentalCheckUp),
+            (at(15, 0), AppointmentType::UrgentDentalAppointment),
+            (at(16, 0), AppointmentType::DentalCheckUp),
+        ] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(date_time, appointment_type))
+                .unwrap();
+        }
+
+        let start_times = |calendar: &DoctorsCalendar| {
+            calendar
+                .iter()
+                .map(|appointment| appointment.date_time)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            calendar.shift_range(at(13, 0), at(17, 0), Duration::minutes(15)),
+            Ok(())
+        );
+        assert_eq!(
+            start_times(&calendar),
+            vec![at(11, 0), at(13, 15), at(14, 45), at(15, 15), at(16, 15)]
+        );
+        assert!(calendar.is_consistent());
+
+        // Another 30 minutes would push the last check-up past 17:00
+        assert_eq!(
+            calendar.shift_range(at(13, 0), at(17, 0), Duration::minutes(30)),
+            Err(CalendarError::OutsideWorkingHours)
+        );
+        assert_eq!(
+            start_times(&calendar),
+            vec![at(11, 0), at(13, 15), at(14, 45), at(15, 15), at(16, 15)]
+        );
+
+        // And moving the morning appointment into the afternoon overlaps
+        assert_eq!(
+            calendar.shift_range(at(11, 0), at(11, 0), Duration::hours(2)),
+            Err(CalendarError::Overlap)
+        );
+        assert_eq!(calendar.len(), 5);
+    }
+
+    // Test the render_day function
+    #[test]
+    fn test_render_day() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+
+        // An empty day has a line per working time slot, and one for the break
+        let agenda = calendar.render_day(date);
+        assert_eq!(agenda.lines().count(), 32 + 1);
+        assert_eq!(agenda.lines().next(), Some("08:00  — free —"));
+        assert_eq!(agenda.lines().nth(16), Some("12:00  — break —"));
+        assert_eq!(agenda.lines().nth(17), Some("13:00  — free —"));
+
+        calendar
+            .add_appointment(
+                DoctorsAppointment::new(
+                    NaiveDateTime::new(date, NaiveTime::from_hms_opt(8, 15, 0).unwrap()),
+                    AppointmentType::DentalCheckUp,
+                )
+                .with_patient(Patient::new("Jane Doe", "555-0100")),
+            )
+            .unwrap();
+
+        let agenda = calendar.render_day(date);
+        assert_eq!(
+            agenda.lines().take(4).collect::<Vec<_>>(),
+            vec![
+                "08:00  — free —",
+                "08:15  Check-up, Jane Doe",
+                "08:30  ...",
+                "08:45  — free —"
+            ]
+        );
+
+        // Weekends have no agenda
+        assert_eq!(
+            calendar.render_day(NaiveDate::from_ymd_opt(2024, 2, 3).unwrap()),
+            ""
+        );
+    }
+
+    // Test the this_week_summary function
+    #[test]
+    fn test_this_week_summary() {
+        let at = |day, hour, minute| {
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, day).unwrap(),
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+            )
+        };
+
+        // Monday at 10:05
+        let mut calendar = DoctorsCalendar::with_clock(Arc::new(FixedClock(at(5, 10, 5))));
+
+        // Before now, this week, and next week
+        for (date_time, appointment_type) in [
+            (at(5, 8, 0), AppointmentType::ImplantConsultation),
+            (at(5, 10, 15), AppointmentType::ImplantConsultation),
+            (at(7, 9, 0), AppointmentType::DentalCheckUp),
+            (at(9, 16, 0), AppointmentType::DentalCheckUp),
+            (at(9, 16, 30), AppointmentType::DentalCheckUp),
+            (at(12, 9, 0), AppointmentType::UrgentDentalAppointment),
+        ] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(date_time, appointment_type))
+                .unwrap();
+        }
+
+        assert_eq!(
+            calendar.this_week_summary(),
+            BTreeMap::from([
+                (AppointmentType::ImplantConsultation, 1),
+                (AppointmentType::DentalCheckUp, 3),
+                (AppointmentType::UrgentDentalAppointment, 0),
+            ])
+        );
+    }
+
+    // Test the duration stored on appointments at booking time
+    #[test]
+    fn test_stored_duration() {
+        let mut calendar = DoctorsCalendar::new();
+        calendar.set_catalog(
+            AppointmentCatalog::new()
+                .with_duration(AppointmentType::DentalCheckUp, Duration::minutes(45)),
+        );
+
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(9, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        let booked = calendar.appointments().next().unwrap().clone();
+        assert_eq!(booked.duration(), Duration::minutes(45));
+        assert_eq!(
+            booked.to_reserved_time_slots(),
+            vec![at(9, 0), at(9, 15), at(9, 30)]
+        );
+
+        // Editing the catalog leaves the booked appointment as it was
+        calendar.set_catalog(
+            AppointmentCatalog::new()
+                .with_duration(AppointmentType::DentalCheckUp, Duration::minutes(60)),
+        );
+        let booked = calendar.appointments().next().unwrap();
+        assert_eq!(
+            booked.to_reserved_time_slots(),
+            vec![at(9, 0), at(9, 15), at(9, 30)]
+        );
+        assert!(calendar.is_slot_free(at(9, 45)));
+
+        // New bookings get the new duration
+        let free_slots = calendar.free_slots(
+            Some(at(9, 0)),
+            Some(at(11, 0)),
+            AppointmentType::DentalCheckUp,
+        );
+        assert_eq!(free_slots.first(), Some(&at(9, 45)));
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert_eq!(
+            calendar.appointments().last().unwrap().end_time(),
+            at(11, 0)
+        );
+    }
+
+    // Test the free_slots_with_margin function
+    #[test]
+    fn test_free_slots_with_margin() {
+        let calendar = DoctorsCalendar::new();
+
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        let slots = calendar.free_slots(from, to, AppointmentType::DentalCheckUp);
+        assert!(slots.contains(&at(11, 15)));
+        assert!(slots.contains(&at(11, 30)));
+
+        let slots = calendar.free_slots_with_margin(
+            from,
+            to,
+            AppointmentType::DentalCheckUp,
+            Duration::minutes(30),
+        );
+
+        // The check-ups must end 30 minutes before the break and the end of
+        // the day
+        assert!(slots.contains(&at(11, 0)));
+        assert!(!slots.contains(&at(11, 15)));
+        assert!(!slots.contains(&at(11, 30)));
+        assert!(slots.contains(&at(13, 0)));
+        assert_eq!(slots.last(), Some(&at(16, 0)));
+
+        // Without margin, nothing is excluded
+        assert_eq!(
+            calendar.free_slots_with_margin(
+                from,
+                to,
+                AppointmentType::DentalCheckUp,
+                Duration::zero()
+            ),
+            calendar.free_slots(from, to, AppointmentType::DentalCheckUp)
+        );
+    }
+
+    // Test the calendars sharing a clinic config
+    #[test]
+    fn test_shared_clinic_config() {
+        let config = Arc::new(ClinicConfig::new(
+            WorkingSchedule::default(),
+            AppointmentCatalog::new(),
+        ));
+        let mut first_doctor = DoctorsCalendar::with_config(config.clone());
+        let second_doctor = DoctorsCalendar::with_config(config.clone());
+
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        assert!(!first_doctor
+            .free_slots(from, to, AppointmentType::DentalCheckUp)
+            .is_empty());
+
+        // Closing the clinic closes both calendars
+        let closure = config.add_closure(date, None);
+        assert_eq!(config.closures(), vec![closure.clone()]);
+        assert!(first_doctor
+            .free_slots(from, to, AppointmentType::DentalCheckUp)
+            .is_empty());
+        assert!(second_doctor
+            .free_slots(from, to, AppointmentType::DentalCheckUp)
+            .is_empty());
+        assert_eq!(
+            first_doctor.add_appointment(DoctorsAppointment::new(
+                at(9, 0),
+                AppointmentType::DentalCheckUp
+            )),
+            Err(CalendarError::Blocked)
+        );
+
+        // A calendar changing its schedule still shares the closures
+        first_doctor.set_schedule(WorkingSchedule::default());
+        assert!(config.remove_closure(&closure));
+        assert!(first_doctor
+            .free_slots(from, to, AppointmentType::DentalCheckUp)
+            .contains(&at(9, 0)));
+        assert!(second_doctor
+            .free_slots(from, to, AppointmentType::DentalCheckUp)
+            .contains(&at(9, 0)));
+        config.add_closure(
+            date,
+            Some((
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+            )),
+        );
+        assert!(!first_doctor.is_slot_free(at(9, 30)));
+        assert!(!second_doctor.is_slot_free(at(9, 30)));
+        assert!(second_doctor.is_slot_free(at(10, 0)));
+    }
+
+    // Test the common_free_slots function
+    #[test]
+    fn test_common_free_slots() {
+        let mut first_doctor = DoctorsCalendar::new();
+        let mut second_doctor = DoctorsCalendar::new();
+
+        let from = Some(at(9, 0));
+        let to = Some(at(11, 0));
+
+        first_doctor
+            .add_appointment(DoctorsAppointment::new(
+                at(9, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        second_doctor
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+
+        // Slots booked on one calendar are excluded, even if free on the other
+        let common = common_free_slots(
+            &first_doctor,
+            &second_doctor,
+            from,
+            to,
+            AppointmentType::DentalCheckUp,
+        );
+        assert!(second_doctor.is_slot_free(at(9, 0)));
+        assert!(!common.contains(&at(9, 0)));
+        assert!(!common.contains(&at(10, 0)));
+        assert_eq!(common, vec![at(9, 30), at(10, 30), at(10, 45), at(11, 0)]);
+
+        // The order of the calendars doesn't matter
+        assert_eq!(
+            common_free_slots(
+                &second_doctor,
+                &first_doctor,
+                from,
+                to,
+                AppointmentType::DentalCheckUp
+            ),
+            common
+        );
+    }
+
+    // Test the next_available_all_types function
+    #[test]
+    fn test_next_available_all_types() {
+        let mut calendar = DoctorsCalendar::new();
+
+        // Leave 30 minute gaps at 9:30 and 11:30
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(8, 0),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 0),
+                AppointmentType::ImplantConsultation,
+            ))
+            .unwrap();
+
+        let next = calendar.next_available_all_types(at(8, 0));
+        assert_eq!(next.len(), 3);
+        assert_eq!(
+            next[&AppointmentType::UrgentDentalAppointment],
+            Some(at(9, 30))
+        );
+        assert_eq!(next[&AppointmentType::DentalCheckUp], Some(at(9, 30)));
+        assert_eq!(next[&AppointmentType::ImplantConsultation], Some(at(13, 0)));
+        assert_eq!(calendar.snapshot().next_available_all_types(at(8, 0)), next);
+    }
+
+    // Test the free_slots_for_duration function
+    #[test]
+    fn test_free_slots_for_duration() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = Some(at(10, 0));
+        let to = Some(at(14, 0));
+
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(10, 30),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+
+        // 11:15 only has 45 minutes before the break
+        let slots = calendar.free_slots_for_duration(from, to, Duration::minutes(60));
+        assert_eq!(
+            slots,
+            vec![
+                at(11, 0),
+                at(13, 0),
+                at(13, 15),
+                at(13, 30),
+                at(13, 45),
+                at(14, 0)
+            ]
+        );
+        assert!(!slots.contains(&at(11, 15)));
+
+        // A 45 minute duration fits there
+        let slots = calendar.free_slots_for_duration(from, to, Duration::minutes(45));
+        assert!(slots.contains(&at(11, 15)));
+        assert!(!slots.contains(&at(11, 30)));
+
+        // Same as the appointment type of the same duration
+        assert_eq!(
+            calendar.free_slots_for_duration(from, to, Duration::minutes(30)),
+            calendar.free_slots(from, to, AppointmentType::DentalCheckUp)
+        );
+    }
+
+    // Test importing appointments of older schema versions
+    #[test]
+    fn test_import_schema_versions() {
+        // Exported appointments have the current schema version
+        let mut calendar = DoctorsCalendar::new();
+        calendar
+            .add_appointment(
+                DoctorsAppointment::new(at(9, 0), AppointmentType::DentalCheckUp)
+                    .with_patient(Patient::new("Jane Doe", "555-0100")),
+            )
+            .unwrap();
+        let mut output = vec![];
+        calendar.export_jsonl(&mut output).unwrap();
+        let exported: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(exported["schema_version"], APPOINTMENT_SCHEMA_VERSION);
+        assert_eq!(exported["patient"]["name"], "Jane Doe");
+
+        // Version 1 appointments only have a date and time and a type
+        let v1 = r#"{"date_time":"2024-02-01T10:00:00","appointment_type":"DentalCheckUp"}"#;
+        let mut calendar = DoctorsCalendar::new();
+        assert_eq!(
+            calendar
+                .import_jsonl(v1.as_bytes(), RoundingPolicy::Reject)
+                .unwrap(),
+            1
+        );
+
+        let appointment = calendar.appointments().next().unwrap();
+        assert_eq!(appointment.date_time, at(10, 0));
+        assert_eq!(appointment.appointment_type, AppointmentType::DentalCheckUp);
+        assert_eq!(appointment.patient, None);
+        assert_eq!(appointment.duration(), Duration::minutes(30));
+        assert!(appointment.tags.is_empty());
+        assert_eq!(appointment.status, AppointmentStatus::Scheduled);
+
+        // Newer schema versions are rejected
+        let v3 = r#"{"schema_version":3,"date_time":"2024-02-01T11:00:00","appointment_type":"DentalCheckUp"}"#;
+        assert!(calendar
+            .import_jsonl(v3.as_bytes(), RoundingPolicy::Reject)
+            .is_err());
+        assert_eq!(calendar.appointments().count(), 1);
+    }
+
+    // Test the exact output of the free_slots_optimized function
+    #[test]
+    fn test_free_slots_optimized_deterministic() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        for (hour, minute, appointment_type) in [
+            (8, 0, AppointmentType::DentalCheckUp),
+            (11, 30, AppointmentType::DentalCheckUp),
+            (14, 0, AppointmentType::UrgentDentalAppointment),
+            (15, 45, AppointmentType::DentalCheckUp),
+        ] {
+            calendar
+                .add_appointment(DoctorsAppointment::new(at(hour, minute), appointment_type))
+                .unwrap();
+        }
+
+        let optimized = calendar.free_slots_optimized(from, to, AppointmentType::DentalCheckUp);
+        // Slots next to the appointments are chosen, and the earliest one among
+        // equally good slots
+        assert_eq!(
+            optimized,
+            vec![
+                at(8, 30),
+                at(9, 0),
+                at(10, 0),
+                at(11, 0),
+                at(13, 0),
+                at(14, 15),
+                at(15, 15),
+                at(16, 15)
+            ]
+        );
+        assert!(optimized.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            calendar.free_slots_optimized(from, to, AppointmentType::DentalCheckUp),
+            optimized
+        );
+        assert_eq!(
+            calendar
+                .clone()
+                .free_slots_optimized(from, to, AppointmentType::DentalCheckUp),
+            optimized
+        );
+    }
+
+    // Test the block_rest_of_day function
+    #[test]
+    fn test_block_rest_of_day() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        let morning = DoctorsAppointment::new(at(9, 0), AppointmentType::DentalCheckUp);
+        let running = DoctorsAppointment::new(at(13, 30), AppointmentType::ImplantConsultation);
+        let afternoon = DoctorsAppointment::new(at(16, 0), AppointmentType::DentalCheckUp);
+        for appointment in [&morning, &running, &afternoon] {
+            calendar.add_appointment(appointment.clone()).unwrap();
+        }
+
+        // The appointments to reschedule are reported, and kept
+        let to_reschedule = calendar.block_rest_of_day(at(14, 0));
+        assert_eq!(to_reschedule, vec![running, afternoon]);
+        assert_eq!(calendar.appointments().count(), 3);
+
+        let slots = calendar.free_slots(from, to, AppointmentType::UrgentDentalAppointment);
+        assert!(slots.iter().all(|slot| *slot < at(13, 30)));
+        assert!(slots.contains(&at(9, 30)));
+
+        // Nothing is blocked after the end of the working day
+        assert!(calendar.block_rest_of_day(at(17, 0)).is_empty());
+        assert_eq!(calendar.blocks().count(), 1);
+    }
+
+    // Test the overlap_report function
+    #[test]
+    fn test_overlap_report() {
+        let mut calendar = DoctorsCalendar::new();
+
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(8, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert_eq!(calendar.overlap_report(), "");
+
+        // Overlap by 5 minutes, then no longer tolerate it
+        calendar
+            .set_overlap_tolerance(Duration::minutes(5))
+            .unwrap();
+        calendar
+            .add_appointment(DoctorsAppointment::new(
+                at(8, 25),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        assert_eq!(calendar.overlap_report(), "");
+        calendar.set_overlap_tolerance(Duration::zero()).unwrap();
+
+        assert_eq!(
+            calendar.overlap_report(),
+            "2024-02-01 08:00-08:30 Check-up overlaps 2024-02-01 08:25-08:55 Check-up by 5 minutes\n"
+        );
+    }
+
+    // Test the queries of a calendar whose schedule has no working time
+    #[test]
+    fn test_empty_schedule() {
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        for schedule in [
+            WorkingSchedule::default().with_working_days(&[]),
+            WorkingSchedule::builder().build().unwrap(),
+        ] {
+            let calendar = DoctorsCalendar::with_schedule(schedule);
+
+            assert!(calendar
+                .free_slots(from, to, AppointmentType::DentalCheckUp)
+                .is_empty());
+            assert!(calendar
+                .available_single_time_slots(at(0, 0), at(23, 59))
+                .is_empty());
+            assert_eq!(
+                calendar.next_available(at(8, 0), AppointmentType::DentalCheckUp),
+                None
+            );
+            assert_eq!(
+                calendar
+                    .schedule()
+                    .working_slots(at(0, 0), at(23, 59))
+                    .count(),
+                0
+            );
+        }
+    }
+
+    // Test cancelled appointments sharing their start time with a booked one
+    #[test]
+    fn test_cancelled_sharing_start_time() {
+        for (cancelled_type, booked_type) in [
+            (
+                AppointmentType::DentalCheckUp,
+                AppointmentType::ImplantConsultation,
+            ),
+            (
+                AppointmentType::ImplantConsultation,
+                AppointmentType::DentalCheckUp,
+            ),
+        ] {
+            let mut calendar = DoctorsCalendar::new();
+            calendar
+                .add_appointment(DoctorsAppointment::new(at(8, 0), cancelled_type))
+                .unwrap();
+            calendar
+                .set_status(at(8, 0), AppointmentStatus::Cancelled)
+                .unwrap();
+            calendar
+                .add_appointment(DoctorsAppointment::new(at(8, 0), booked_type))
+                .unwrap();
+
+            // The export loads back, whatever the order of the appointments
+            let mut output = vec![];
+            calendar.export_jsonl(&mut output).unwrap();
+            let mut imported_calendar = DoctorsCalendar::new();
+            assert_eq!(
+                imported_calendar
+                    .import_jsonl(output.as_slice(), RoundingPolicy::Reject)
+                    .unwrap(),
+                2
+            );
+            assert!(imported_calendar.appointments().eq(calendar.appointments()));
+            assert!(!imported_calendar.is_slot_free(at(8, 0)));
+
+            // The booked appointment is the one changed and cancelled
+            calendar
+                .change_type(at(8, 0), AppointmentType::UrgentDentalAppointment)
+                .unwrap();
+            assert!(calendar.is_slot_free(at(8, 15)));
+
+            let cancellation = calendar.cancel_appointment(at(8, 0)).unwrap();
+            assert_eq!(
+                cancellation.appointment.appointment_type,
+                AppointmentType::UrgentDentalAppointment
+            );
+            assert_eq!(
+                cancellation.appointment.status,
+                AppointmentStatus::Scheduled
+            );
+            assert!(calendar.is_slot_free(at(8, 0)));
+            assert_eq!(calendar.appointments().count(), 1);
+        }
+    }
+
+    // Test the snapshots and versions of calendars sharing a clinic config
+    #[test]
+    fn test_clinic_config_snapshot() {
+        let config = Arc::new(ClinicConfig::default());
+        let calendar = DoctorsCalendar::with_config(config.clone());
+
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let from = Some(at(0, 0));
+        let to = Some(at(23, 59));
+
+        let snapshot = calendar.snapshot();
+        let version = calendar.version();
+        let free_slots = snapshot.free_slots(from, to, AppointmentType::DentalCheckUp);
+        assert!(!free_slots.is_empty());
+
+        // The snapshot keeps the closures it was taken with
+        let closure = config.add_closure(date, None);
+        assert!(calendar
+            .free_slots(from, to, AppointmentType::DentalCheckUp)
+            .is_empty());
+        assert_eq!(
+            snapshot.free_slots(from, to, AppointmentType::DentalCheckUp),
+            free_slots
+        );
+
+        // The calendars using the config change version
+        assert!(calendar.version() > version);
+        let version = calendar.version();
+        assert!(config.remove_closure(&closure));
+        assert!(calendar.version() > version);
+        assert!(!config.remove_closure(&closure));
+    }
+
+    // Test the free_slots_spans function with a duration set in the catalog
+    #[test]
+    fn test_free_slots_spans_catalog_duration() {
+        let mut calendar = DoctorsCalendar::new();
+        calendar.set_catalog(
+            AppointmentCatalog::new()
+                .with_duration(AppointmentType::DentalCheckUp, Duration::minutes(45)),
+        );
+
+        let spans = calendar.free_slots_spans(
+            Some(at(8, 0)),
+            Some(at(8, 15)),
+            AppointmentType::DentalCheckUp,
+        );
+        assert_eq!(spans, vec![(at(8, 0), at(8, 45)), (at(8, 15), at(9, 0))]);
+
+        // The optimizer counts the capacity with the catalog duration too
+        let explained = calendar.free_slots_optimized_explained(
+            Some(at(8, 0)),
+            Some(at(8, 45)),
+            AppointmentType::UrgentDentalAppointment,
+        );
+        // The 45 minutes after 8:00 fit one check-up, with no time left
+        assert_eq!(explained.len(), 1);
+        assert_eq!(explained[0].capacity[&AppointmentType::DentalCheckUp], 1);
+        assert_eq!(
+            explained[0].capacity[&AppointmentType::UrgentDentalAppointment],
+            0
+        );
+    }
+
+    // Test the appointments sharing their start time
+    #[test]
+    fn test_same_start_time() {
+        let mut calendar = DoctorsCalendar::new();
+
+        let check_up = DoctorsAppointment::new(at(8, 0), AppointmentType::DentalCheckUp);
+
+        // A second appointment starting at the same time is rejected
+        calendar.add_appointment(check_up.clone()).unwrap();
+        assert_eq!(
+            calendar.add_appointment(check_up.clone()),
+            Err(CalendarError::Overlap)
+        );
+        assert_eq!(
+            calendar.add_appointment(DoctorsAppointment::new(
+                at(8, 0),
+                AppointmentType::UrgentDentalAppointment
+            )),
+            Err(CalendarError::Overlap)
+        );
+        assert_eq!(calendar.len(), 1);
+
+        // Cancelled appointments with the same value are kept apart
+        calendar
+            .set_status(at(8, 0), AppointmentStatus::Cancelled)
+            .unwrap();
+        calendar.add_appointment(check_up.clone()).unwrap();
+        calendar
+            .set_status(at(8, 0), AppointmentStatus::Cancelled)
+            .unwrap();
+        assert_eq!(calendar.len(), 2);
+
+        let cancelled: Vec<&DoctorsAppointment> = calendar.appointments().collect();
+        assert_eq!(cancelled[0], cancelled[1]);
+        assert!(!cancelled[0].is_same_appointment(cancelled[1]));
+        assert!(calendar.is_slot_free(at(8, 0)));
+    }
 }
@@ -0,0 +1,116 @@
+//! Tests for the config module.
+use appointment_booking::config::*;
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use appointment_booking::{
+        appointment::{AppointmentType, Block},
+        catalog::AppointmentCatalog,
+        error::{ConfigError, ScheduleError},
+        schedule::WorkingSchedule,
+    };
+    use chrono::{Duration, NaiveDate, NaiveTime, Weekday};
+
+    use super::*;
+
+    // Test the from_file function
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join("appointment_booking_test_from_file.json");
+        fs::write(
+            &path,
+            r#"{
+                "schedule": {
+                    "windows": [["09:00:00", "12:30:00"], ["13:30:00", "18:00:00"]],
+                    "days": ["Mon", "Tue", "Thu"]
+                },
+                "holidays": ["2024-12-25", "2024-12-26"],
+                "slot_minutes": 15,
+                "durations_minutes": { "DentalCheckUp": 45, "ImplantConsultation": 60 }
+            }"#,
+        )
+        .unwrap();
+
+        let config = ClinicConfig::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let expected = ClinicConfig::new(
+            WorkingSchedule::builder()
+                .window(
+                    NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                    NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+                )
+                .window(
+                    NaiveTime::from_hms_opt(13, 30, 0).unwrap(),
+                    NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                )
+                .working_days(&[Weekday::Mon, Weekday::Tue, Weekday::Thu])
+                .build()
+                .unwrap(),
+            AppointmentCatalog::new()
+                .with_duration(AppointmentType::DentalCheckUp, Duration::minutes(45))
+                .with_duration(AppointmentType::ImplantConsultation, Duration::minutes(60)),
+        );
+        expected.add_closure(NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(), None);
+        expected.add_closure(NaiveDate::from_ymd_opt(2024, 12, 26).unwrap(), None);
+
+        assert_eq!(config.schedule(), expected.schedule());
+        assert_eq!(config.catalog(), expected.catalog());
+        assert_eq!(config.closures(), expected.closures());
+        assert_eq!(
+            config.closures().first(),
+            Some(&Block::closure(
+                NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(),
+                None
+            ))
+        );
+    }
+
+    // Test the from_file function with invalid files
+    #[test]
+    fn test_from_file_errors() {
+        let load = |name: &str, contents: &str| {
+            let path = std::env::temp_dir().join(format!("appointment_booking_test_{}.json", name));
+            fs::write(&path, contents).unwrap();
+            let result = ClinicConfig::from_file(&path);
+            fs::remove_file(&path).unwrap();
+
+            result
+        };
+
+        assert!(matches!(
+            ClinicConfig::from_file("missing_clinic_config.json"),
+            Err(ConfigError::Io(_))
+        ));
+        assert!(matches!(
+            load(
+                "unknown_field",
+                r#"{ "schedule": { "windows": [], "days": [] }, "doctors": 2 }"#
+            ),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            load(
+                "empty_window",
+                r#"{ "schedule": { "windows": [["12:00:00", "08:00:00"]], "days": ["Mon"] } }"#
+            ),
+            Err(ConfigError::Schedule(ScheduleError::EmptyWindow { .. }))
+        ));
+        assert!(matches!(
+            load(
+                "slot_size",
+                r#"{ "schedule": { "windows": [], "days": [] }, "slot_minutes": 10 }"#
+            ),
+            Err(ConfigError::UnsupportedSlotSize(10))
+        ));
+        assert!(matches!(
+            load(
+                "duration",
+                r#"{ "schedule": { "windows": [], "days": [] }, "durations_minutes": { "DentalCheckUp": 40 } }"#
+            ),
+            Err(ConfigError::InvalidDuration(AppointmentType::DentalCheckUp))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+//! Tests for the free_slots_cache module.
+use appointment_booking::free_slots_cache::*;
+
+#[cfg(test)]
+mod tests {
+    use appointment_booking::appointment::{AppointmentType, DoctorsAppointment, DoctorsCalendar};
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+    use super::*;
+
+    // Test extending the range of a cached query
+    #[test]
+    fn test_extend_cached_range() {
+        let mut cache = FreeSlotsCache::new(DoctorsCalendar::new());
+
+        // Monday to Wednesday
+        let monday = NaiveDate::from_ymd_opt(2024, 2, 5).unwrap();
+        let wednesday = NaiveDate::from_ymd_opt(2024, 2, 7).unwrap();
+        let friday = NaiveDate::from_ymd_opt(2024, 2, 9).unwrap();
+
+        let slots = cache.free_slots(monday, wednesday, AppointmentType::DentalCheckUp);
+        assert_eq!(cache.computed_days(), 3);
+        assert_eq!(
+            slots,
+            cache.calendar().free_slots(
+                Some(NaiveDateTime::new(monday, NaiveTime::MIN)),
+                Some(NaiveDateTime::new(
+                    wednesday,
+                    NaiveTime::from_hms_opt(23, 59, 0).unwrap()
+                )),
+                AppointmentType::DentalCheckUp
+            )
+        );
+
+        // Only Thursday and Friday are computed
+        let extended = cache.free_slots(monday, friday, AppointmentType::DentalCheckUp);
+        assert_eq!(cache.computed_days(), 5);
+        assert!(extended.starts_with(&slots));
+        assert_eq!(
+            extended,
+            cache.calendar().free_slots(
+                Some(NaiveDateTime::new(monday, NaiveTime::MIN)),
+                Some(NaiveDateTime::new(
+                    friday,
+                    NaiveTime::from_hms_opt(23, 59, 0).unwrap()
+                )),
+                AppointmentType::DentalCheckUp
+            )
+        );
+
+        // Other appointment types are cached separately
+        cache.free_slots(monday, monday, AppointmentType::UrgentDentalAppointment);
+        assert_eq!(cache.computed_days(), 6);
+    }
+
+    // Test the invalidation of cached days when booking and cancelling
+    #[test]
+    fn test_invalidate_on_changes() {
+        let mut cache = FreeSlotsCache::new(DoctorsCalendar::new());
+
+        let monday = NaiveDate::from_ymd_opt(2024, 2, 5).unwrap();
+        let tuesday = NaiveDate::from_ymd_opt(2024, 2, 6).unwrap();
+        let at = |hour, minute| {
+            NaiveDateTime::new(tuesday, NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+        };
+
+        let slots = cache.free_slots(monday, tuesday, AppointmentType::DentalCheckUp);
+        assert!(slots.contains(&at(9, 0)));
+        assert_eq!(cache.computed_days(), 2);
+
+        // Only Tuesday is computed again
+        cache
+            .add_appointment(DoctorsAppointment::new(
+                at(9, 0),
+                AppointmentType::DentalCheckUp,
+            ))
+            .unwrap();
+        let slots = cache.free_slots(monday, tuesday, AppointmentType::DentalCheckUp);
+        assert!(!slots.contains(&at(9, 0)));
+        assert_eq!(cache.computed_days(), 3);
+
+        // Failed bookings don't invalidate anything
+        assert!(cache
+            .add_appointment(DoctorsAppointment::new(
+                at(9, 0),
+                AppointmentType::DentalCheckUp
+            ))
+            .is_err());
+        cache.free_slots(monday, tuesday, AppointmentType::DentalCheckUp);
+        assert_eq!(cache.computed_days(), 3);
+
+        cache.cancel_appointment(at(9, 0)).unwrap();
+        let slots = cache.free_slots(monday, tuesday, AppointmentType::DentalCheckUp);
+        assert!(slots.contains(&at(9, 0)));
+        assert_eq!(cache.computed_days(), 4);
+    }
+}
@@ -0,0 +1,261 @@
+//! Tests for the schedule module.
+use appointment_booking::schedule::*;
+
+#[cfg(test)]
+mod tests {
+    use appointment_booking::error::ScheduleError;
+    use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+    use super::*;
+
+    // Test the default schedule
+    #[test]
+    fn test_default_schedule() {
+        let schedule = WorkingSchedule::default();
+
+        assert_eq!(schedule.windows().len(), 2);
+        assert!(schedule.is_working_hour(NaiveTime::from_hms_opt(11, 45, 0).unwrap()));
+        assert!(!schedule.is_working_hour(NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+        assert!(schedule.is_working_hour(NaiveTime::from_hms_opt(13, 0, 0).unwrap()));
+        assert!(!schedule.is_working_hour(NaiveTime::from_hms_opt(17, 0, 0).unwrap()));
+
+        // Thursday
+        assert!(schedule.is_working_day(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+        // Saturday
+        assert!(!schedule.is_working_day(NaiveDate::from_ymd_opt(2024, 2, 3).unwrap()));
+
+        assert_eq!(
+            schedule
+                .day_time_slots(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())
+                .len(),
+            32
+        );
+    }
+
+    // Test the continuous function
+    #[test]
+    fn test_continuous() {
+        let schedule = WorkingSchedule::continuous(
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        )
+        .unwrap();
+
+        assert!(schedule.is_working_hour(NaiveTime::from_hms_opt(12, 30, 0).unwrap()));
+        assert_eq!(
+            schedule
+                .day_time_slots(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())
+                .len(),
+            36
+        );
+
+        // No break to run into
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(11, 30, 0).unwrap(),
+        );
+        assert_eq!(
+            schedule.end_time_respecting_breaks(date, Duration::minutes(90)),
+            Ok(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+            ))
+        );
+    }
+
+    // Test the with_break function
+    #[test]
+    fn test_with_break() {
+        let schedule = WorkingSchedule::with_break(
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+            Duration::minutes(30),
+        )
+        .unwrap();
+
+        assert!(schedule.is_working_hour(NaiveTime::from_hms_opt(12, 15, 0).unwrap()));
+        assert!(!schedule.is_working_hour(NaiveTime::from_hms_opt(12, 45, 0).unwrap()));
+        assert_eq!(
+            schedule
+                .day_time_slots(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())
+                .len(),
+            34
+        );
+
+        // The next working time slot after the start of the break
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(12, 15, 0).unwrap(),
+        );
+        assert_eq!(
+            schedule.next_working_datetime(date, None),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap()
+            )
+        );
+    }
+
+    // Test the builder function
+    #[test]
+    fn test_builder() {
+        let schedule = WorkingSchedule::builder()
+            .window(
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            )
+            .working_days(&[Weekday::Sat])
+            .build()
+            .unwrap();
+
+        // Thursday
+        assert!(!schedule.is_working_day(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+
+        // Friday evening moves to Saturday morning
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+        );
+        assert_eq!(
+            schedule.next_working_datetime(date, None),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 3).unwrap(),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+            )
+        );
+    }
+
+    // Test the schedules without working time
+    #[test]
+    fn test_empty_schedule() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        let date_time = NaiveDateTime::new(date, NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+
+        for schedule in [
+            WorkingSchedule::default().with_working_days(&[]),
+            WorkingSchedule::builder().build().unwrap(),
+        ] {
+            assert_eq!(
+                schedule
+                    .working_slots(date_time, date_time + Duration::weeks(1))
+                    .count(),
+                0
+            );
+            assert!(!schedule.is_working_day_and_hour(date_time));
+            assert_eq!(
+                schedule.next_working_datetime(date_time, None),
+                NaiveDateTime::MAX
+            );
+        }
+
+        // One-off windows are still working time
+        let mut schedule = WorkingSchedule::default().with_working_days(&[]);
+        schedule
+            .extend_day(
+                date,
+                (
+                    NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                    NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                ),
+            )
+            .unwrap();
+        assert_eq!(schedule.day_time_slots(date).len(), 4);
+    }
+
+    // Test the build function with invalid working hours windows
+    #[test]
+    fn test_build_invalid_windows() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let eleven = NaiveTime::from_hms_opt(11, 0, 0).unwrap();
+        let twelve = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        let seventeen = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+
+        assert_eq!(
+            WorkingSchedule::builder()
+                .window(nine, twelve)
+                .window(eleven, seventeen)
+                .build(),
+            Err(ScheduleError::OverlappingWindows {
+                previous: (nine, twelve),
+                next: (eleven, seventeen),
+            })
+        );
+
+        assert_eq!(
+            WorkingSchedule::builder()
+                .window(twelve, seventeen)
+                .window(nine, eleven)
+                .build(),
+            Err(ScheduleError::UnorderedWindows {
+                previous: (twelve, seventeen),
+                next: (nine, eleven),
+            })
+        );
+
+        assert_eq!(
+            WorkingSchedule::continuous(seventeen, nine),
+            Err(ScheduleError::EmptyWindow {
+                start: seventeen,
+                end: nine,
+            })
+        );
+
+        // Back-to-back windows are fine
+        assert!(WorkingSchedule::builder()
+            .window(nine, twelve)
+            .window(twelve, seventeen)
+            .build()
+            .is_ok());
+    }
+
+    // Test serializing and deserializing a schedule
+    #[test]
+    fn test_schedule_serde() {
+        let schedule = WorkingSchedule::default().with_working_days(&[Weekday::Sun, Weekday::Mon]);
+
+        let json = serde_json::to_string(&schedule).unwrap();
+        assert_eq!(
+            json,
+            r#"{"windows":[["08:00:00","12:00:00"],["13:00:00","17:00:00"]],"days":["Sun","Mon"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<WorkingSchedule>(&json).unwrap(),
+            schedule
+        );
+
+        // Invalid windows are rejected
+        let json =
+            r#"{"windows":[["09:00:00","12:00:00"],["11:00:00","17:00:00"]],"days":["Mon"]}"#;
+        assert!(serde_json::from_str::<WorkingSchedule>(json).is_err());
+    }
+
+    // Test the extend_day function
+    #[test]
+    fn test_extend_day() {
+        let mut schedule = WorkingSchedule::default();
+
+        let time = |hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2024, 2, 3).unwrap();
+
+        // A non-working day only gets the one-off window
+        schedule.extend_day(saturday, (time(9), time(11))).unwrap();
+        assert_eq!(schedule.windows_on(saturday), vec![(time(9), time(11))]);
+        assert_eq!(schedule.day_time_slots(saturday).len(), 8);
+        assert!(schedule.is_working_day_and_hour(saturday.and_time(time(10))));
+        assert!(!schedule.is_working_day_and_hour(saturday.and_time(time(14))));
+
+        // Overlapping one-off windows are merged with the regular ones
+        let thursday = NaiveDate::from_ymd_opt(2024, 2, 1).unwrap();
+        schedule.extend_day(thursday, (time(11), time(13))).unwrap();
+        assert_eq!(schedule.windows_on(thursday), vec![(time(8), time(17))]);
+        assert_eq!(schedule.longest_window(), Duration::hours(9));
+
+        // The one-off windows are persisted
+        let json = serde_json::to_string(&schedule).unwrap();
+        assert_eq!(
+            serde_json::from_str::<WorkingSchedule>(&json).unwrap(),
+            schedule
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Tests for the utils module.
+use appointment_booking::utils::*;
+
+#[cfg(test)]
+mod tests {
+    use appointment_booking::error::CalendarError;
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+    use super::*;
+
+    // Test the next_15_mark_from function
+    #[test]
+    fn test_next_15_mark_from() {
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(18, 12, 30).unwrap(),
+        );
+        assert_eq!(
+            next_15_mark_from(date),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                NaiveTime::from_hms_opt(18, 15, 0).unwrap()
+            )
+        );
+
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(23, 50, 0).unwrap(),
+        );
+        assert_eq!(
+            next_15_mark_from(date),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap()
+            )
+        );
+    }
+
+    // Test the FixedClock always returns the injected time
+    #[test]
+    fn test_fixed_clock() {
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let clock = FixedClock(date);
+        assert_eq!(clock.now(), date);
+        assert_eq!(clock.now(), date);
+    }
+
+    // Test the format_slot function
+    #[test]
+    fn test_format_slot() {
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        assert_eq!(format_slot(date, DEFAULT_SLOT_FORMAT), "2024-02-01 08:00");
+        assert_eq!(format_slot(date, "%d/%m/%Y %H:%M"), "01/02/2024 08:00");
+
+        // Round-trips through the CLI input format
+        assert_eq!(
+            NaiveDateTime::parse_from_str(
+                &format_slot(date, DEFAULT_SLOT_FORMAT),
+                DEFAULT_SLOT_FORMAT
+            )
+            .unwrap(),
+            date
+        );
+    }
+
+    // Test the check_range function
+    #[test]
+    fn test_check_range() {
+        let from = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        );
+        let to = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        );
+
+        assert_eq!(check_range(from, to), Ok(()));
+        assert_eq!(check_range(from, from), Ok(()));
+        assert_eq!(
+            check_range(to, from),
+            Err(CalendarError::InvertedRange { from: to, to: from })
+        );
+    }
+
+    // Test the end_of_week_from function
+    #[test]
+    fn test_end_of_week_from() {
+        // Monday the 29th of January to Sunday the 4th of February
+        for (day, friday) in [(29, 2), (30, 2), (31, 2), (1, 2), (2, 2), (3, 9), (4, 9)] {
+            let month = if day > 20 { 1 } else { 2 };
+            let date = NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, month, day).unwrap(),
+                NaiveTime::from_hms_opt(10, 30, 0).unwrap(),
+            );
+
+            assert_eq!(
+                end_of_week_from(date),
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2024, 2, friday).unwrap(),
+                    NaiveTime::from_hms_opt(17, 0, 0).unwrap()
+                ),
+                "{}",
+                date
+            );
+        }
+
+        // After the end of the working day on Friday, the working week is over
+        let date = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2024, 2, 2).unwrap(),
+            NaiveTime::from_hms_opt(17, 30, 0).unwrap(),
+        );
+        assert_eq!(
+            end_of_week_from(date),
+            NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2024, 2, 9).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap()
+            )
+        );
+    }
+}